@@ -0,0 +1,157 @@
+use alloc::collections::{vec_deque, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f64::consts::LN_2;
+use core::hash::{BuildHasher, Hash};
+
+use crate::{DefaultHashBuilder, Push};
+
+/// A queue that suppresses duplicates using a counting Bloom filter instead of a hash table.
+///
+/// The filter takes about ten bytes per value at a false positive rate of one percent, much less
+/// than the index of a `FIFOSet`, at the cost of being approximate: it can mistake a value for
+/// one that is queued. **Pushing such a value ignores it, so some unique values are dropped.** The
+/// chance of this is the false positive rate the queue was created with, as long as it holds no
+/// more than the expected number of values. It grows quickly beyond that. A value that is queued
+/// is never mistaken for one that isn't, so no duplicates are queued.
+#[derive(Clone)]
+pub struct BloomFIFOSet<T, S = DefaultHashBuilder> {
+    values: VecDeque<T>,
+    /// A counter for every bit of a regular Bloom filter. A counter that reached the maximum stays
+    /// there, because it is no longer known how many values it counts.
+    counters: Vec<u8>,
+    hashes: u32,
+    hasher: S,
+}
+
+#[cfg(feature = "std")]
+impl<T: Hash> BloomFIFOSet<T> {
+    /// Create an empty queue that mistakes about `false_positive_rate` of the values pushed for
+    /// queued ones, while it holds at most `expected_len` values.
+    ///
+    /// Panics if `false_positive_rate` is not between zero and one.
+    pub fn new(expected_len: usize, false_positive_rate: f64) -> Self {
+        Self::with_hasher(expected_len, false_positive_rate, DefaultHashBuilder::new())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> BloomFIFOSet<T, S> {
+    /// Like `new`, but the queue will use the given hash builder to hash values.
+    pub fn with_hasher(expected_len: usize, false_positive_rate: f64, hasher: S) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "false positive rate {} is not between zero and one",
+            false_positive_rate,
+        );
+
+        // The optimal size and number of hash functions of a Bloom filter, rounded up and to the
+        // nearest integer by casting.
+        let expected_len = expected_len.max(1) as f64;
+        let len = (-expected_len * ln(false_positive_rate) / (LN_2 * LN_2)) as usize + 1;
+        let hashes = (len as f64 / expected_len * LN_2 + 0.5) as u32;
+
+        Self {
+            values: VecDeque::new(),
+            counters: vec![0; len],
+            hashes: hashes.max(1),
+            hasher,
+        }
+    }
+
+    /// Add an item to the back of the queue, unless it is, or is mistaken for, a queued item.
+    ///
+    /// Returns `Push::Ignored` with the item otherwise.
+    pub fn push(&mut self, element: T) -> Push<T> {
+        if self.contains(&element) {
+            return Push::Ignored(element);
+        }
+
+        for counter in self.counters(&element) {
+            self.counters[counter] = self.counters[counter].saturating_add(1);
+        }
+        self.values.push_back(element);
+
+        Push::Added
+    }
+
+    /// Retrieve the item that has been in the queue longest.
+    pub fn pop(&mut self) -> Option<T> {
+        let element = self.values.pop_front()?;
+        for counter in self.counters(&element) {
+            if self.counters[counter] < u8::MAX {
+                self.counters[counter] -= 1;
+            }
+        }
+
+        Some(element)
+    }
+
+    /// Whether an item might be queued.
+    ///
+    /// `false` means that the item is not queued. `true` means that it is queued, or that it is
+    /// mistaken for a queued item.
+    pub fn contains(&self, x: &T) -> bool {
+        self.counters(x).all(|counter| self.counters[counter] > 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.counters.fill(0);
+    }
+
+    /// The counters of a value, derived from a single hash by double hashing.
+    fn counters(&self, x: &T) -> impl Iterator<Item = usize> {
+        let hash = self.hasher.hash_one(x);
+        let (first, second) = (hash as u32 as u64, (hash >> 32) | 1);
+        let len = self.counters.len() as u64;
+
+        (0..self.hashes as u64).map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % len) as usize)
+    }
+}
+
+impl<T, S> BloomFIFOSet<T, S> {
+    pub fn peek(&self) -> Option<&T> {
+        self.values.front()
+    }
+
+    /// An iterator over the values, front to back.
+    pub fn iter(&self) -> vec_deque::Iter<'_, T> {
+        self.values.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// The natural logarithm of a positive, normal number, which `core` doesn't provide.
+fn ln(x: f64) -> f64 {
+    // Write `x` as `m * 2^e` with `m` between one and two, and take the series of
+    // `ln(m) = 2 * atanh((m - 1) / (m + 1))`, which converges quickly for such `m`.
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7FF) as i64 - 1023;
+    let m = f64::from_bits(bits & ((1 << 52) - 1) | 1023 << 52);
+
+    let y = (m - 1.0) / (m + 1.0);
+    let mut power = y;
+    let mut sum = 0.0;
+    for k in 0..20 {
+        sum += power / (2 * k + 1) as f64;
+        power *= y * y;
+    }
+
+    exponent as f64 * LN_2 + 2.0 * sum
+}
+
+impl<'a, T, S> IntoIterator for &'a BloomFIFOSet<T, S> {
+    type Item = &'a T;
+    type IntoIter = vec_deque::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
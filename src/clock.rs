@@ -0,0 +1,69 @@
+#[cfg(target_has_atomic = "ptr")]
+use alloc::sync::Arc;
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// A source of the current time, used to expire values pushed with a time to live.
+///
+/// The time is measured from an arbitrary, fixed point in the past. It should never go backwards.
+pub trait Clock {
+    fn now(&self) -> Duration;
+}
+
+/// How a queue shares its clock with the queues split off from it.
+///
+/// Targets without atomic pointers have no `Arc`, so the clock is shared through an `Rc` there.
+/// That makes every queue on such a target neither `Send` nor `Sync`, whether or not it has a
+/// clock, which matters little without threads.
+#[cfg(target_has_atomic = "ptr")]
+pub(crate) type Shared<T> = Arc<T>;
+#[cfg(not(target_has_atomic = "ptr"))]
+pub(crate) type Shared<T> = alloc::rc::Rc<T>;
+
+/// The monotonic clock of the operating system.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        static EPOCH: OnceLock<Instant> = OnceLock::new();
+        EPOCH.get_or_init(Instant::now).elapsed()
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+///
+/// Clones share their time, so a test can keep one clone to advance a clock it gave to a queue.
+///
+/// Only available on targets with 64-bit atomics.
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let nanos = u64::try_from(by.as_nanos()).expect("duration too long");
+        self.nanos.fetch_add(nanos, Ordering::SeqCst);
+    }
+}
+
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+}
@@ -0,0 +1,556 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::{FIFOSet, Push, WorkQueue};
+
+/// A `FIFOSet` that can be shared between threads.
+///
+/// Consumers can block until a value is available. Once the queue is closed, no new values are
+/// accepted, and consumers drain the values that are still queued before `pop` returns `None`.
+///
+/// Like a `Mutex`, the queue is poisoned by a panic while it is locked, for example in the `Hash`
+/// implementation of a value or because of `OverflowPolicy::Panic`. The queue may be left
+/// inconsistent, so every later operation panics as well.
+pub struct ConcurrentFIFOSet<T, S = RandomState> {
+    state: Mutex<State<T, S>>,
+    available: Condvar,
+}
+
+struct State<T, S> {
+    set: FIFOSet<T, S>,
+    closed: bool,
+}
+
+/// A `WorkQueue` that can be shared between workers on different threads.
+///
+/// Workers block in `get` until an item is available. Once the queue is shut down, no new items
+/// are accepted, and workers drain the items that are still queued before `get` returns `None`.
+/// Items that are processing when the queue is shut down can still be marked done or requeued.
+///
+/// Like a `ConcurrentFIFOSet`, the queue is poisoned by a panic while it is locked.
+pub struct ConcurrentWorkQueue<T, S = RandomState> {
+    state: Mutex<WorkState<T, S>>,
+    available: Condvar,
+}
+
+struct WorkState<T, S> {
+    queue: WorkQueue<T, S>,
+    shut_down: bool,
+}
+
+const POISONED: &str = "queue poisoned by a panic while it was locked";
+
+/// Returned by `ConcurrentFIFOSet::push` when the queue is closed. Contains the pushed value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Closed<T>(pub T);
+
+/// Returned by `ConcurrentFIFOSet::try_pop` when no value can be taken right away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryPopError {
+    /// The queue is empty, but values might still be pushed.
+    Empty,
+    /// The queue is empty and closed.
+    Closed,
+}
+
+/// Returned by `ConcurrentFIFOSet::pop_timeout` when no value was taken in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PopTimeoutError {
+    /// The queue stayed empty until the timeout passed.
+    Timeout,
+    /// The queue is empty and closed.
+    Closed,
+}
+
+impl<T: Eq + Hash> ConcurrentFIFOSet<T> {
+    pub fn new() -> Self {
+        Self::from(FIFOSet::new())
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> ConcurrentFIFOSet<T, S> {
+    /// Add an item to the queue, unless the queue is closed.
+    ///
+    /// Behaves like `FIFOSet::push` otherwise.
+    pub fn push(&self, element: T) -> Result<Push<T>, Closed<T>> {
+        let mut state = self.lock();
+        if state.closed {
+            return Err(Closed(element));
+        }
+
+        let pushed = state.set.push(element);
+        drop(state);
+        self.available.notify_one();
+
+        Ok(pushed)
+    }
+
+    /// Retrieve the item that has been in the queue longest, waiting for one if the queue is
+    /// empty.
+    ///
+    /// Returns `None` once the queue is closed and empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut state = self.lock();
+        loop {
+            if let Some(value) = state.set.pop() {
+                return Some(value);
+            }
+            if state.closed {
+                return None;
+            }
+
+            state = self.available.wait(state).expect(POISONED);
+        }
+    }
+
+    /// Like `pop`, but gives up once `timeout` has passed.
+    pub fn pop_timeout(&self, timeout: Duration) -> Result<T, PopTimeoutError> {
+        // A timeout too long to represent never passes.
+        let deadline = Instant::now().checked_add(timeout);
+
+        let mut state = self.lock();
+        loop {
+            if let Some(value) = state.set.pop() {
+                return Ok(value);
+            }
+            if state.closed {
+                return Err(PopTimeoutError::Closed);
+            }
+
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(PopTimeoutError::Timeout);
+                    }
+
+                    self.available.wait_timeout(state, deadline - now)
+                        .expect(POISONED)
+                        .0
+                }
+                None => self.available.wait(state).expect(POISONED),
+            };
+        }
+    }
+
+    /// Retrieve the item that has been in the queue longest, without waiting.
+    pub fn try_pop(&self) -> Result<T, TryPopError> {
+        let mut state = self.lock();
+        match state.set.pop() {
+            Some(value) => Ok(value),
+            None if state.closed => Err(TryPopError::Closed),
+            None => Err(TryPopError::Empty),
+        }
+    }
+
+    pub fn contains(&self, x: &T) -> bool {
+        self.lock().set.contains(x)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, S> ConcurrentFIFOSet<T, S> {
+    /// Stop accepting new values, and wake up all consumers waiting for one.
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.available.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+
+    /// Unwrap the queue, returning the values that were still queued.
+    pub fn into_inner(self) -> FIFOSet<T, S> {
+        self.state.into_inner().expect(POISONED).set
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T, S>> {
+        self.state.lock().expect(POISONED)
+    }
+}
+
+impl<T, S> From<FIFOSet<T, S>> for ConcurrentFIFOSet<T, S> {
+    fn from(set: FIFOSet<T, S>) -> Self {
+        Self {
+            state: Mutex::new(State { set, closed: false }),
+            available: Condvar::new(),
+        }
+    }
+}
+
+impl<T, S: Default> Default for ConcurrentFIFOSet<T, S> {
+    fn default() -> Self {
+        Self::from(FIFOSet::default())
+    }
+}
+
+impl<T: Clone + Eq + Hash> ConcurrentWorkQueue<T> {
+    pub fn new() -> Self {
+        Self::from(WorkQueue::new())
+    }
+}
+
+impl<T: Clone + Eq + Hash, S: BuildHasher + Clone> ConcurrentWorkQueue<T, S> {
+    /// Add an item to the queue, or mark it dirty if it is processing, unless the queue is shut
+    /// down.
+    ///
+    /// Returns whether the item wasn't queued or dirty already.
+    pub fn push(&self, item: T) -> Result<bool, Closed<T>> {
+        let mut state = self.lock();
+        if state.shut_down {
+            return Err(Closed(item));
+        }
+
+        let pushed = state.queue.push(item);
+        drop(state);
+        self.available.notify_one();
+
+        Ok(pushed)
+    }
+
+    /// Take the item that has been in the queue longest and mark it processing, waiting for one
+    /// if the queue is empty.
+    ///
+    /// Returns `None` once the queue is shut down and empty.
+    pub fn get(&self) -> Option<T> {
+        let mut state = self.lock();
+        loop {
+            if let Some(item) = state.queue.get() {
+                return Some(item);
+            }
+            if state.shut_down {
+                return None;
+            }
+
+            state = self.available.wait(state).expect(POISONED);
+        }
+    }
+
+    /// Take the item that has been in the queue longest and mark it processing, without waiting.
+    pub fn try_get(&self) -> Result<T, TryPopError> {
+        let mut state = self.lock();
+        match state.queue.get() {
+            Some(item) => Ok(item),
+            None if state.shut_down => Err(TryPopError::Closed),
+            None => Err(TryPopError::Empty),
+        }
+    }
+
+    /// Mark an item as no longer processing. If it was pushed while processing, queue it.
+    ///
+    /// Returns whether the item was processing.
+    pub fn done(&self, item: &T) -> bool {
+        let done = self.lock().queue.done(item);
+        self.available.notify_one();
+
+        done
+    }
+
+    /// Mark an item as no longer processing and queue it again right away.
+    ///
+    /// Returns whether the item was processing.
+    pub fn requeue(&self, item: &T) -> bool {
+        let requeued = self.lock().queue.requeue(item);
+        self.available.notify_one();
+
+        requeued
+    }
+
+    pub fn is_processing(&self, item: &T) -> bool {
+        self.lock().queue.is_processing(item)
+    }
+
+    /// Whether the item is queued or dirty, that is, whether it still needs to be processed.
+    pub fn is_pending(&self, item: &T) -> bool {
+        self.lock().queue.is_pending(item)
+    }
+
+    /// The number of items in the queue, not counting dirty items.
+    pub fn len(&self) -> usize {
+        self.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of items that are processing.
+    pub fn processing_len(&self) -> usize {
+        self.lock().queue.processing_len()
+    }
+}
+
+impl<T, S> ConcurrentWorkQueue<T, S> {
+    /// Stop accepting new items, and wake up all workers waiting for one.
+    pub fn shut_down(&self) {
+        self.lock().shut_down = true;
+        self.available.notify_all();
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.lock().shut_down
+    }
+
+    /// Unwrap the queue, with the items that were still queued, dirty or processing.
+    pub fn into_inner(self) -> WorkQueue<T, S> {
+        self.state.into_inner().expect(POISONED).queue
+    }
+
+    fn lock(&self) -> MutexGuard<'_, WorkState<T, S>> {
+        self.state.lock().expect(POISONED)
+    }
+}
+
+impl<T, S> From<WorkQueue<T, S>> for ConcurrentWorkQueue<T, S> {
+    fn from(queue: WorkQueue<T, S>) -> Self {
+        Self {
+            state: Mutex::new(WorkState { queue, shut_down: false }),
+            available: Condvar::new(),
+        }
+    }
+}
+
+impl<T, S: Default> Default for ConcurrentWorkQueue<T, S> {
+    fn default() -> Self {
+        Self::from(WorkQueue::default())
+    }
+}
+
+impl<T> fmt::Display for Closed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("pushing onto a closed queue")
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for Closed<T> {}
+
+impl fmt::Display for TryPopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryPopError::Empty => f.write_str("popping from an empty queue"),
+            TryPopError::Closed => f.write_str("popping from an empty and closed queue"),
+        }
+    }
+}
+
+impl std::error::Error for TryPopError {}
+
+impl fmt::Display for PopTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PopTimeoutError::Timeout => f.write_str("timed out waiting on an empty queue"),
+            PopTimeoutError::Closed => f.write_str("popping from an empty and closed queue"),
+        }
+    }
+}
+
+impl std::error::Error for PopTimeoutError {}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use crate::{FIFOSet, OverflowPolicy, Push};
+
+    use super::{Closed, ConcurrentFIFOSet, ConcurrentWorkQueue, PopTimeoutError, TryPopError};
+
+    /// Long enough for a spawned thread to block, in all likelihood.
+    const PAUSE: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn push_wakes_consumer() {
+        let set = Arc::new(ConcurrentFIFOSet::new());
+        let consumer = {
+            let set = Arc::clone(&set);
+            thread::spawn(move || set.pop())
+        };
+
+        thread::sleep(PAUSE);
+        assert_eq!(set.push(1), Ok(Push::Added));
+        assert_eq!(consumer.join().unwrap(), Some(1));
+    }
+
+    #[test]
+    fn close_drains() {
+        let set = Arc::new(ConcurrentFIFOSet::new());
+        for value in 0..10 {
+            set.push(value).unwrap();
+        }
+
+        let consumers = (0..3)
+            .map(|_| {
+                let set = Arc::clone(&set);
+                thread::spawn(move || {
+                    let mut popped = Vec::new();
+                    while let Some(value) = set.pop() {
+                        popped.push(value);
+                    }
+                    popped
+                })
+            })
+            .collect::<Vec<_>>();
+
+        thread::sleep(PAUSE);
+        set.close();
+
+        let mut popped = consumers.into_iter()
+            .flat_map(|consumer| consumer.join().unwrap())
+            .collect::<Vec<_>>();
+        popped.sort();
+        assert_eq!(popped, (0..10).collect::<Vec<_>>());
+        assert_eq!(set.pop(), None);
+        assert_eq!(set.try_pop(), Err(TryPopError::Closed));
+    }
+
+    #[test]
+    fn push_after_close() {
+        let set = ConcurrentFIFOSet::new();
+        set.push(1).unwrap();
+        set.close();
+
+        assert!(set.is_closed());
+        assert_eq!(set.push(2), Err(Closed(2)));
+        assert_eq!(set.pop(), Some(1));
+    }
+
+    #[test]
+    fn try_pop() {
+        let set = ConcurrentFIFOSet::new();
+        assert_eq!(set.try_pop(), Err(TryPopError::Empty));
+        set.push(1).unwrap();
+        assert_eq!(set.try_pop(), Ok(1));
+    }
+
+    #[test]
+    fn pop_timeout() {
+        let set = Arc::new(ConcurrentFIFOSet::<u32>::new());
+        assert_eq!(set.pop_timeout(Duration::from_millis(10)), Err(PopTimeoutError::Timeout));
+
+        set.push(1).unwrap();
+        assert_eq!(set.pop_timeout(Duration::from_millis(10)), Ok(1));
+
+        // A timeout too long to represent waits until the queue is closed.
+        let consumer = {
+            let set = Arc::clone(&set);
+            thread::spawn(move || set.pop_timeout(Duration::MAX))
+        };
+        thread::sleep(PAUSE);
+        set.close();
+        assert_eq!(consumer.join().unwrap(), Err(PopTimeoutError::Closed));
+    }
+
+    #[test]
+    fn panic_poisons() {
+        let set = ConcurrentFIFOSet::from(FIFOSet::bounded(1, OverflowPolicy::Panic));
+        set.push(1).unwrap();
+
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| set.push(2))).is_err());
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| set.len())).is_err());
+    }
+
+    #[test]
+    fn work_queue_hands_out_once() {
+        const ITEMS: usize = 8;
+
+        let queue = Arc::new(ConcurrentWorkQueue::<usize>::new());
+        let processing = Arc::new((0..ITEMS).map(|_| AtomicBool::new(false)).collect::<Vec<_>>());
+        let processed = Arc::new(AtomicUsize::new(0));
+
+        let workers = (0..4)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let processing = Arc::clone(&processing);
+                let processed = Arc::clone(&processed);
+                thread::spawn(move || {
+                    while let Some(item) = queue.get() {
+                        let twice = processing[item].swap(true, Ordering::SeqCst);
+                        assert!(!twice, "item {} handed out twice", item);
+                        thread::yield_now();
+                        processing[item].store(false, Ordering::SeqCst);
+                        processed.fetch_add(1, Ordering::SeqCst);
+                        assert!(queue.done(&item));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for round in 0..100 {
+            queue.push(round % ITEMS).unwrap();
+        }
+        while !queue.is_empty() || queue.processing_len() > 0 {
+            thread::yield_now();
+        }
+
+        queue.shut_down();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert!(processed.load(Ordering::SeqCst) >= ITEMS);
+        assert!(queue.is_shut_down());
+        assert_eq!(queue.push(0), Err(Closed(0)));
+        assert_eq!(queue.try_get(), Err(TryPopError::Closed));
+    }
+
+    #[test]
+    fn work_queue_shut_down_drains() {
+        let queue = Arc::new(ConcurrentWorkQueue::new());
+        for item in 0..3 {
+            queue.push(item).unwrap();
+        }
+        assert_eq!(queue.get(), Some(0));
+
+        let worker = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || {
+                let mut items = Vec::new();
+                while let Some(item) = queue.get() {
+                    items.push(item);
+                    queue.done(&item);
+                }
+                items
+            })
+        };
+
+        thread::sleep(PAUSE);
+        queue.shut_down();
+        assert_eq!(worker.join().unwrap(), [1, 2]);
+
+        // An item processing during the shut down can still be marked done.
+        assert!(queue.done(&0));
+        assert_eq!(queue.processing_len(), 0);
+    }
+
+    #[test]
+    fn get_waits_for_done() {
+        let queue = Arc::new(ConcurrentWorkQueue::new());
+        queue.push(1).unwrap();
+        assert_eq!(queue.get(), Some(1));
+        assert_eq!(queue.push(1), Ok(true));
+
+        let worker = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || queue.get())
+        };
+
+        thread::sleep(PAUSE);
+        assert!(queue.done(&1));
+        assert_eq!(worker.join().unwrap(), Some(1));
+    }
+}
@@ -0,0 +1,150 @@
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::iter::FromIterator;
+
+use crate::map::{self, FIFOMap};
+use crate::DefaultHashBuilder;
+
+/// A `FIFOSet` that counts how many times each queued value was pushed.
+///
+/// Pushing a value that is already queued doesn't move it, but increments its count. The count
+/// is returned along with the value when it is popped.
+#[derive(Clone)]
+pub struct CountingFIFOSet<T, S = DefaultHashBuilder> {
+    counts: FIFOMap<T, usize, S>,
+}
+
+#[cfg(feature = "std")]
+impl<T: Eq + Hash> CountingFIFOSet<T> {
+    pub fn new() -> Self {
+        Self { counts: FIFOMap::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { counts: FIFOMap::with_capacity(capacity) }
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> CountingFIFOSet<T, S> {
+    /// Create an empty queue which will use the given hash builder to hash values.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self { counts: FIFOMap::with_hasher(hasher) }
+    }
+
+    /// Add an item to the back of the queue, or count it again if it is already queued.
+    ///
+    /// Returns the number of times the item was pushed since it was queued, including this time.
+    pub fn push(&mut self, element: T) -> usize {
+        match self.counts.get_mut(&element) {
+            Some(count) => {
+                *count += 1;
+                *count
+            }
+            None => {
+                self.counts.push(element, 1);
+                1
+            }
+        }
+    }
+
+    /// Retrieve the item that has been in the queue longest, with the number of times it was
+    /// pushed.
+    pub fn pop(&mut self) -> Option<(T, usize)> {
+        self.counts.pop()
+    }
+
+    /// The item that `pop` would return, with its count.
+    pub fn peek(&self) -> Option<(&T, usize)> {
+        self.counts.peek().map(|(element, &count)| (element, count))
+    }
+
+    /// The number of times a queued item was pushed, or zero if it isn't queued.
+    pub fn count_of<Q: ?Sized + Hash + Eq>(&self, x: &Q) -> usize
+    where
+        T: Borrow<Q>,
+    {
+        self.counts.get(x).copied().unwrap_or(0)
+    }
+
+    pub fn contains<Q: ?Sized + Hash + Eq>(&self, x: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.counts.contains_key(x)
+    }
+
+    /// Remove an item from the queue, wherever it is, with its count.
+    pub fn remove_value<Q: ?Sized + Hash + Eq>(&mut self, x: &Q) -> Option<(T, usize)>
+    where
+        T: Borrow<Q>,
+    {
+        self.counts.remove(x)
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+    }
+}
+
+impl<T, S> CountingFIFOSet<T, S> {
+    /// An iterator over the items and their counts, front to back.
+    pub fn iter(&self) -> map::Iter<'_, T, usize> {
+        self.counts.iter()
+    }
+
+    /// The number of distinct items in the queue.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+}
+
+impl<T, S: Default> Default for CountingFIFOSet<T, S> {
+    fn default() -> Self {
+        Self { counts: FIFOMap::default() }
+    }
+}
+
+impl<A: Eq + Hash, S: BuildHasher + Default> FromIterator<A> for CountingFIFOSet<A, S> {
+    fn from_iter<T: IntoIterator<Item=A>>(iter: T) -> Self {
+        let mut set = Self::default();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<A: Eq + Hash, S: BuildHasher> Extend<A> for CountingFIFOSet<A, S> {
+    fn extend<T: IntoIterator<Item=A>>(&mut self, iter: T) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: fmt::Debug, S> fmt::Debug for CountingFIFOSet<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T, S> IntoIterator for CountingFIFOSet<T, S> {
+    type Item = (T, usize);
+    type IntoIter = map::IntoIter<T, usize>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.counts.into_iter()
+    }
+}
+
+impl<'a, T, S> IntoIterator for &'a CountingFIFOSet<T, S> {
+    type Item = (&'a T, &'a usize);
+    type IntoIter = map::Iter<'a, T, usize>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -0,0 +1,82 @@
+use core::hash::{BuildHasher, Hash};
+
+use crate::{FIFOSet, Place, Push};
+
+/// A value in a `FIFOSet` that may or may not be queued, created by `FIFOSet::entry`.
+pub enum Entry<'a, T, S> {
+    Occupied(OccupiedEntry<'a, T, S>),
+    Vacant(VacantEntry<'a, T, S>),
+}
+
+/// A value that is queued.
+pub struct OccupiedEntry<'a, T, S> {
+    pub(crate) set: &'a mut FIFOSet<T, S>,
+    pub(crate) key: usize,
+}
+
+/// A value that is not queued.
+pub struct VacantEntry<'a, T, S> {
+    pub(crate) set: &'a mut FIFOSet<T, S>,
+    pub(crate) hash: u64,
+    pub(crate) value: T,
+}
+
+impl<T: Eq + Hash, S: BuildHasher> OccupiedEntry<'_, T, S> {
+    /// The queued value.
+    pub fn get(&self) -> &T {
+        &self.set.values[self.key].value
+    }
+
+    /// The index of the queued value.
+    pub fn position(&self) -> usize {
+        self.set.order.index(self.set.values[self.key].position)
+    }
+
+    /// Remove the queued value from the queue.
+    pub fn remove(self) -> T {
+        self.set.order.remove(self.set.values[self.key].position);
+        self.set.take(self.key)
+    }
+
+    /// Move the queued value to the back of the queue.
+    pub fn move_to_back(self) {
+        self.relocate(Place::Back);
+    }
+
+    /// Move the queued value to the front of the queue, so that it is popped next.
+    pub fn move_to_front(self) {
+        self.relocate(Place::Front);
+    }
+
+    fn relocate(self, place: Place) {
+        self.set.order.remove(self.set.values[self.key].position);
+        self.set.compact_if_needed();
+        self.set.place(self.key, place);
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> VacantEntry<'_, T, S> {
+    /// The value that would be added.
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Take back the value without adding it.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    /// Add the value to the back of the queue.
+    ///
+    /// Overflow is handled like it is by `FIFOSet::push`.
+    pub fn insert(self) -> Push<T> {
+        self.set.add_new(self.hash, self.value, None, Place::Back)
+    }
+
+    /// Add the value to the front of the queue, so that it is popped next.
+    ///
+    /// Overflow is handled like it is by `FIFOSet::push_front`.
+    pub fn insert_front(self) -> Push<T> {
+        self.set.add_new(self.hash, self.value, None, Place::Front)
+    }
+}
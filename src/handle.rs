@@ -0,0 +1,11 @@
+/// Refers to a value in a `FIFOSet` for as long as it is queued, created by
+/// `FIFOSet::push_handle`.
+///
+/// Unlike an index, a handle doesn't change when values in front of it are popped. Once the value
+/// leaves the queue, the handle is stale and no longer refers to any value, not even to a value
+/// that is stored in its place later. A handle only refers to values in the queue that created it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle {
+    pub(crate) key: usize,
+    pub(crate) seq: u64,
+}
@@ -0,0 +1,139 @@
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::time::Duration;
+
+use crate::{Clock, DefaultHashBuilder, DuplicatePolicy, FIFOSet, Iter, OverflowPolicy, Push};
+
+/// A `FIFOSet` that remembers the items it recently handed out, and ignores pushes of those items.
+///
+/// The history holds the last `limit` popped items, so it doesn't grow without bounds. With a
+/// time to live, popped items are also forgotten once it has passed.
+#[derive(Clone)]
+pub struct HistoryFIFOSet<T, S = DefaultHashBuilder> {
+    queue: FIFOSet<T, S>,
+    /// Popped items, oldest first.
+    history: FIFOSet<T, S>,
+    ttl: Option<Duration>,
+}
+
+#[cfg(feature = "std")]
+impl<T: Clone + Eq + Hash> HistoryFIFOSet<T> {
+    /// Create an empty queue that remembers the last `limit` popped items.
+    pub fn new(limit: usize) -> Self {
+        Self::with_hasher(limit, None, DefaultHashBuilder::new())
+    }
+
+    /// Create an empty queue that remembers the last `limit` popped items, for at most `ttl`.
+    pub fn with_ttl(limit: usize, ttl: Duration) -> Self {
+        Self::with_hasher(limit, Some(ttl), DefaultHashBuilder::new())
+    }
+}
+
+impl<T: Clone + Eq + Hash, S: BuildHasher + Clone> HistoryFIFOSet<T, S> {
+    /// Create an empty queue that remembers the last `limit` popped items, for at most `ttl` if
+    /// given, which will use the given hash builder to hash values.
+    pub fn with_hasher(limit: usize, ttl: Option<Duration>, hasher: S) -> Self {
+        let mut history = FIFOSet::bounded_with_hasher(limit, OverflowPolicy::DropOldest, hasher.clone());
+        // Popping an item again makes it the most recent one.
+        history.set_duplicate_policy(DuplicatePolicy::MoveToBack);
+
+        Self {
+            queue: FIFOSet::with_hasher(hasher),
+            history,
+            ttl,
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash, S: BuildHasher> HistoryFIFOSet<T, S> {
+    /// Use the given clock to decide when popped items are forgotten.
+    pub fn set_clock(&mut self, clock: impl Clock + Send + Sync + 'static) {
+        self.history.set_clock(clock);
+    }
+
+    /// Add an item to the queue, unless it was popped recently.
+    ///
+    /// An item that is queued or was popped recently is returned in `Push::Ignored`.
+    pub fn push(&mut self, element: T) -> Push<T> {
+        if self.history.contains(&element) {
+            return Push::Ignored(element);
+        }
+
+        self.queue.push(element)
+    }
+
+    /// Retrieve the item that has been in the queue longest, and remember it.
+    pub fn pop(&mut self) -> Option<T> {
+        let element = self.queue.pop()?;
+        match self.ttl {
+            Some(ttl) => self.history.push_with_ttl(element.clone(), ttl),
+            None => self.history.push(element.clone()),
+        };
+
+        Some(element)
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.queue.peek()
+    }
+
+    pub fn contains<Q: ?Sized + Hash + Eq>(&self, x: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.queue.contains(x)
+    }
+
+    /// Whether an item was popped recently, so that pushing it is ignored.
+    pub fn was_recently_seen<Q: ?Sized + Hash + Eq>(&self, x: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.history.contains(x)
+    }
+
+    /// Forget that an item was popped, so that it can be pushed again.
+    ///
+    /// Returns whether the item was popped recently.
+    pub fn forget<Q: ?Sized + Hash + Eq>(&mut self, x: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.history.remove_value(x).is_some()
+    }
+
+    /// Forget all popped items.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+}
+
+impl<T, S> HistoryFIFOSet<T, S> {
+    /// An iterator over the queued items, front to back.
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.queue.iter()
+    }
+
+    /// The number of queued items, not counting the items that were popped recently.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// The number of items that were popped recently.
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+}
+
+impl<'a, T, S> IntoIterator for &'a HistoryFIFOSet<T, S> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -0,0 +1,272 @@
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use core::hash::{BuildHasher, Hash};
+use core::iter::{Chain, FusedIterator};
+use core::time::Duration;
+
+use crate::{is_expired, FIFOSet, Node};
+use crate::order::Keys;
+use crate::slab::Slab;
+
+/// An iterator over the values of a `FIFOSet`, front to back.
+pub struct Iter<'a, T> {
+    pub(crate) keys: Keys<'a>,
+    pub(crate) values: &'a Slab<Node<T>>,
+    pub(crate) expiries: &'a BTreeMap<usize, Duration>,
+    /// Values that expired at or before this time are skipped.
+    pub(crate) cutoff: Option<Duration>,
+    pub(crate) remaining: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining > 0 {
+            let key = self.keys.next()?;
+            if !is_expired(self.expiries, key, self.cutoff) {
+                self.remaining -= 1;
+                return Some(&self.values[key].value);
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while self.remaining > 0 {
+            let key = self.keys.next_back()?;
+            if !is_expired(self.expiries, key, self.cutoff) {
+                self.remaining -= 1;
+                return Some(&self.values[key].value);
+            }
+        }
+
+        None
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            keys: self.keys.clone(),
+            values: self.values,
+            expiries: self.expiries,
+            cutoff: self.cutoff,
+            remaining: self.remaining,
+        }
+    }
+}
+
+/// An iterator over the items removed from a `FIFOSet` by `FIFOSet::drain`.
+pub struct Drain<T> {
+    pub(crate) values: vec::IntoIter<T>,
+}
+
+impl<T> Iterator for Drain<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.values.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for Drain<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.values.next_back()
+    }
+}
+
+impl<T> ExactSizeIterator for Drain<T> {}
+
+impl<T> FusedIterator for Drain<T> {}
+
+/// An iterator that removes the items of a `FIFOSet` matching a predicate, created by
+/// `FIFOSet::extract_if`.
+pub struct ExtractIf<'a, T, S, F> {
+    pub(crate) set: &'a mut FIFOSet<T, S>,
+    /// Index of the next item to visit.
+    pub(crate) index: usize,
+    pub(crate) extract: F,
+}
+
+impl<T: Eq + Hash, S: BuildHasher, F: FnMut(&T) -> bool> Iterator for ExtractIf<'_, T, S, F> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Works on `order` directly, because skipping items that expire during the iteration
+        // would make the index unreliable.
+        while let Some(position) = self.set.order.position(self.index) {
+            let key = self.set.order.key(position);
+            if (self.extract)(&self.set.values[key].value) {
+                self.set.order.remove(position);
+                return Some(self.set.take(key));
+            }
+            self.index += 1;
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.set.order.len() - self.index))
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher, F: FnMut(&T) -> bool> FusedIterator for ExtractIf<'_, T, S, F> {}
+
+/// An owning iterator over the values of a `FIFOSet`, front to back.
+pub struct IntoIter<T> {
+    pub(crate) keys: vec::IntoIter<usize>,
+    pub(crate) values: Slab<Node<T>>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.keys.next().map(|key| self.values.remove(key).value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.keys.next_back().map(|key| self.values.remove(key).value)
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+/// A lazy iterator over the items of a `FIFOSet` that are not in another, created by
+/// `FIFOSet::difference`.
+pub struct Difference<'a, T, S> {
+    pub(crate) iter: Iter<'a, T>,
+    pub(crate) other: &'a FIFOSet<T, S>,
+}
+
+impl<'a, T: Eq + Hash, S: BuildHasher> Iterator for Difference<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|x| !other.contains(x))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> FusedIterator for Difference<'_, T, S> {}
+
+impl<T, S> Clone for Difference<'_, T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+            other: self.other,
+        }
+    }
+}
+
+/// A lazy iterator over the items of a `FIFOSet` that are also in another, created by
+/// `FIFOSet::intersection`.
+pub struct Intersection<'a, T, S> {
+    pub(crate) iter: Iter<'a, T>,
+    pub(crate) other: &'a FIFOSet<T, S>,
+}
+
+impl<'a, T: Eq + Hash, S: BuildHasher> Iterator for Intersection<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|x| other.contains(x))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> FusedIterator for Intersection<'_, T, S> {}
+
+impl<T, S> Clone for Intersection<'_, T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+            other: self.other,
+        }
+    }
+}
+
+/// A lazy iterator over the items of a `FIFOSet`, followed by the items of another that are not
+/// in the first, created by `FIFOSet::union`.
+pub struct Union<'a, T, S> {
+    pub(crate) iter: Chain<Iter<'a, T>, Difference<'a, T, S>>,
+}
+
+impl<'a, T: Eq + Hash, S: BuildHasher> Iterator for Union<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> FusedIterator for Union<'_, T, S> {}
+
+impl<T, S> Clone for Union<'_, T, S> {
+    fn clone(&self) -> Self {
+        Self { iter: self.iter.clone() }
+    }
+}
+
+/// A lazy iterator over the items of a `FIFOSet` that are not in another, followed by the items
+/// of the other that are not in the first, created by `FIFOSet::symmetric_difference`.
+pub struct SymmetricDifference<'a, T, S> {
+    pub(crate) iter: Chain<Difference<'a, T, S>, Difference<'a, T, S>>,
+}
+
+impl<'a, T: Eq + Hash, S: BuildHasher> Iterator for SymmetricDifference<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> FusedIterator for SymmetricDifference<'_, T, S> {}
+
+impl<T, S> Clone for SymmetricDifference<'_, T, S> {
+    fn clone(&self) -> Self {
+        Self { iter: self.iter.clone() }
+    }
+}
@@ -1,130 +1,1370 @@
-use std::collections::{HashSet, VecDeque};
-use std::collections::vec_deque::Iter;
-use std::hash::Hash;
-use std::iter::FromIterator;
-use std::ops::{Index, RangeBounds};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::iter::FromIterator;
+use core::ops::{Bound, Index, RangeBounds};
+use core::time::Duration;
+
+use hashbrown::HashTable;
+
+pub use crate::bloom::BloomFIFOSet;
+pub use crate::clock::Clock;
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+pub use crate::clock::ManualClock;
+#[cfg(feature = "std")]
+pub use crate::clock::SystemClock;
+use crate::clock::Shared;
+#[cfg(feature = "std")]
+pub use crate::concurrent::{
+    Closed, ConcurrentFIFOSet, ConcurrentWorkQueue, PopTimeoutError, TryPopError,
+};
+pub use crate::counting::CountingFIFOSet;
+pub use crate::entry::{Entry, OccupiedEntry, VacantEntry};
+pub use crate::handle::Handle;
+pub use crate::history::HistoryFIFOSet;
+pub use crate::iter::{
+    Difference, Drain, ExtractIf, Intersection, IntoIter, Iter, SymmetricDifference, Union,
+};
+pub use crate::map::FIFOMap;
+use crate::order::Order;
+#[cfg(feature = "std")]
+pub use crate::persistent::{Codec, PersistentFIFOSet};
+pub use crate::push::{DuplicatePolicy, OverflowPolicy, Push};
+use crate::slab::Slab;
+pub use crate::work_queue::WorkQueue;
+
+mod bloom;
+mod clock;
+#[cfg(feature = "std")]
+mod concurrent;
+mod counting;
+mod entry;
+mod handle;
+mod history;
+mod iter;
+pub mod map;
+mod order;
+#[cfg(feature = "std")]
+mod persistent;
+mod push;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod slab;
+mod work_queue;
+
+/// The hash builder that queues use by default, the same `RandomState` that `HashSet` uses.
+#[cfg(feature = "std")]
+pub type DefaultHashBuilder = std::collections::hash_map::RandomState;
+
+/// Without the `std` feature there is no default hash builder, so one has to be passed to
+/// `FIFOSet::with_hasher` and the like. This type only fills in the default type parameter, and
+/// has no values.
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Copy, Debug)]
+pub enum DefaultHashBuilder {}
 
 /// A FIFO queue with unique values.
-#[derive(Clone, Default)]
-pub struct FIFOSet<T> {
-    deq: VecDeque<T>,
-    set: HashSet<T>,
+///
+/// Each value is stored once, in `values`, under a key that doesn't change while the value is
+/// queued. The queue order is kept in `order` as a sequence of those keys, and `set` indexes the
+/// keys by the hash of their value. Every value knows its position in `order`, so it can be
+/// removed without searching for it.
+///
+/// Values are hashed with a `BuildHasher` of type `S`, which defaults to the same `RandomState`
+/// that `HashSet` uses. Without the `std` feature, there is no default and a hash builder has to
+/// be passed in.
+///
+/// A queue can be bounded, in which case it holds at most `limit` values and pushing a new value
+/// onto a full queue is handled by its `OverflowPolicy`. Pushing a value that is already queued is
+/// handled by its `DuplicatePolicy`, which by default ignores the pushed value.
+///
+/// Values pushed with a time to live expire once it has passed, according to the `Clock` of the
+/// queue. Expired values are invisible: they are skipped when reading from the queue and removed
+/// from it before the next modification.
+#[derive(Clone)]
+pub struct FIFOSet<T, S = DefaultHashBuilder> {
+    values: Slab<Node<T>>,
+    order: Order,
+    set: HashTable<usize>,
+    hasher: S,
+    limit: Option<usize>,
+    overflow: OverflowPolicy,
+    duplicate: DuplicatePolicy,
+    /// Uses the `SystemClock` if not set.
+    clock: Option<Shared<dyn Clock + Send + Sync>>,
+    /// The expiry time and key of every value that has a time to live.
+    deadlines: BTreeSet<(Duration, usize)>,
+    /// The expiry time of every value that has a time to live, by key. Kept apart from the values,
+    /// so that queues without a time to live don't pay for it per value.
+    expiries: BTreeMap<usize, Duration>,
+    /// The keys by the sequence number of their value.
+    seqs: BTreeMap<u64, usize>,
+    /// The sequence number of the next value to be stored. Never goes down, so that handles to
+    /// removed values don't refer to values stored under the same key later.
+    next_seq: u64,
+}
+
+#[derive(Clone)]
+pub(crate) struct Node<T> {
+    pub(crate) value: T,
+    position: usize,
+    seq: u64,
+}
+
+/// Where `FIFOSet::add` puts a new value.
+#[derive(Clone, Copy)]
+enum Place {
+    Back,
+    Front,
+    Index(usize),
 }
 
+#[cfg(feature = "std")]
 impl<T: Eq + Hash> FIFOSet<T> {
     pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::new())
+    }
+
+    /// Create an empty queue that holds at most `limit` values.
+    pub fn bounded(limit: usize, overflow: OverflowPolicy) -> Self {
+        Self::bounded_with_hasher(limit, overflow, DefaultHashBuilder::new())
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> FIFOSet<T, S> {
+    /// Create an empty queue which will use the given hash builder to hash values.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::with_capacity_and_hasher(0, hasher)
+    }
+
+    /// Create an empty queue with space for at least `capacity` values, which will use the given
+    /// hash builder to hash values.
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
         Self {
-            deq: VecDeque::new(),
-            set: HashSet::new(),
+            values: Slab::with_capacity(capacity),
+            order: Order::with_capacity(capacity),
+            set: HashTable::with_capacity(capacity),
+            hasher,
+            limit: None,
+            overflow: OverflowPolicy::Panic,
+            duplicate: DuplicatePolicy::Ignore,
+            clock: None,
+            deadlines: BTreeSet::new(),
+            expiries: BTreeMap::new(),
+            seqs: BTreeMap::new(),
+            next_seq: 0,
         }
     }
 
-    pub fn with_capacity(capacity: usize) -> Self {
+    /// Create an empty queue that holds at most `limit` values, which will use the given hash
+    /// builder to hash values.
+    pub fn bounded_with_hasher(limit: usize, overflow: OverflowPolicy, hasher: S) -> Self {
         Self {
-            deq: VecDeque::with_capacity(capacity),
-            set: HashSet::with_capacity(capacity),
+            limit: Some(limit),
+            overflow,
+            ..Self::with_hasher(hasher)
         }
     }
 
-    pub fn get(&self, index: usize) -> Option<&T> {
-        self.deq.get(index)
+    pub fn hasher(&self) -> &S {
+        &self.hasher
     }
 
-    pub fn swap(&mut self, i: usize, j: usize) {
-        self.deq.swap(i, j)
+    /// The maximum number of values in the queue, if it is bounded.
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
     }
 
-    pub fn capacity(&self) -> usize {
-        self.deq.capacity()
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow
     }
 
-    pub fn reserve(&mut self, additional: usize) {
-        self.deq.reserve(additional);
-        self.set.reserve(additional);
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate
     }
 
-    pub fn iter(&self) -> Iter<'_, T> {
-        self.deq.iter()
+    /// Set how `push` handles values that are already queued.
+    pub fn set_duplicate_policy(&mut self, duplicate: DuplicatePolicy) {
+        self.duplicate = duplicate;
     }
 
-    pub fn len(&self) -> usize {
-        self.deq.len()
+    /// Set the clock that decides when values pushed with a time to live expire.
+    pub fn set_clock(&mut self, clock: impl Clock + Send + Sync + 'static) {
+        self.clock = Some(Shared::new(clock));
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.deq.is_empty()
+    pub fn is_full(&self) -> bool {
+        self.limit.is_some_and(|limit| self.len() >= limit)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.nth(index)
+    }
+
+    pub fn swap(&mut self, i: usize, j: usize) {
+        self.purge_expired();
+
+        let len = self.len();
+        let out_of_bounds = |index| panic!("index {} out of bounds for length {}", index, len);
+        let a = self.order.position(i).unwrap_or_else(|| out_of_bounds(i));
+        let b = self.order.position(j).unwrap_or_else(|| out_of_bounds(j));
+
+        self.order.swap(a, b);
+        self.values[self.order.key(a)].position = a;
+        self.values[self.order.key(b)].position = b;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.order.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.values.reserve(additional);
+        self.order.reserve(additional);
+
+        let Self { values, set, hasher, .. } = self;
+        set.reserve(additional, |&key| hasher.hash_one(&values[key].value));
     }
 
     pub fn range<R: RangeBounds<usize>>(&self, range: R) -> Iter<'_, T> {
-        self.deq.range(range)
+        let cutoff = self.expiry_cutoff();
+        let (start, end) = bounds(range, self.order.len() - self.expired_count(cutoff));
+
+        // Translate to indices in `order`, which also counts the expired values.
+        let (first, last) = match cutoff {
+            None => (start, end),
+            Some(_) => {
+                let mut live = self.order.iter()
+                    .enumerate()
+                    .filter(|&(_, key)| !self.is_expired(key, cutoff))
+                    .map(|(index, _)| index)
+                    .chain(core::iter::once(self.order.len()));
+                let first = live.nth(start).unwrap();
+                let last = if end == start { first } else { live.nth(end - start - 1).unwrap() };
+                (first, last)
+            }
+        };
+
+        Iter {
+            keys: self.order.range(first, last),
+            values: &self.values,
+            expiries: &self.expiries,
+            cutoff,
+            remaining: end - start,
+        }
     }
 
     pub fn clear(&mut self) {
-        self.deq.clear();
+        self.values.clear();
+        self.order.clear();
         self.set.clear();
+        self.deadlines.clear();
+        self.expiries.clear();
+        self.seqs.clear();
+    }
+
+    pub fn contains<Q: ?Sized + Hash + Eq>(&self, x: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.find(x).is_some_and(|key| self.is_live(key))
     }
 
-    pub fn contains(&self, x: &T) -> bool {
-        self.set.contains(x)
+    /// The queued item equal to `x`, which may differ from `x` in ways that `Eq` ignores.
+    pub fn get_value<Q: ?Sized + Hash + Eq>(&self, x: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+    {
+        let key = self.find(x).filter(|&key| self.is_live(key))?;
+        Some(&self.values[key].value)
     }
 
     pub fn peek(&self) -> Option<&T> {
-        self.deq.front()
+        match self.expiry_cutoff() {
+            None => self.order.front().map(|key| &self.values[key].value),
+            Some(_) => self.iter().next(),
+        }
+    }
+
+    /// Add an item to the queue.
+    ///
+    /// If the item is already queued, the duplicate policy decides what happens. If the queue is
+    /// full, the overflow policy does. A queued item keeps its time to live, also when the
+    /// duplicate policy moves or replaces it.
+    pub fn push(&mut self, element: T) -> Push<T> {
+        self.push_with(element, self.duplicate)
+    }
+
+    /// Add an item to the queue, handling a duplicate with the given policy instead of the policy
+    /// of the queue.
+    pub fn push_with(&mut self, element: T, duplicate: DuplicatePolicy) -> Push<T> {
+        self.add(element, duplicate, None, Place::Back)
+    }
+
+    /// Add an item to the queue that expires after `ttl`.
+    ///
+    /// Pushing an item that is already queued follows the duplicate policy. When the pushed item
+    /// takes the place of the queued one, the queued item takes on the new time to live.
+    ///
+    /// Without the `std` feature, this panics unless a clock was set with `set_clock`.
+    pub fn push_with_ttl(&mut self, element: T, ttl: Duration) -> Push<T> {
+        // A deadline too far in the future to represent never passes.
+        let expires = self.now().checked_add(ttl).unwrap_or(Duration::MAX);
+        self.add(element, self.duplicate, Some(expires), Place::Back)
+    }
+
+    /// Add an item to the front of the queue, so that it is popped next.
+    ///
+    /// Duplicates and overflow are handled like they are by `push`, except that
+    /// `DuplicatePolicy::MoveToBack` moves the queued item to the front.
+    pub fn push_front(&mut self, element: T) -> Push<T> {
+        self.add(element, self.duplicate, None, Place::Front)
+    }
+
+    /// Add an item to the queue at `index`, moving the items behind it back by one.
+    ///
+    /// Duplicates and overflow are handled like they are by `push`, except that
+    /// `DuplicatePolicy::MoveToBack` moves the queued item to `index`. If the overflow policy
+    /// evicts the front item, the new item ends up one place further to the front.
+    ///
+    /// Panics if `index` is larger than the length of the queue.
+    pub fn insert(&mut self, index: usize, element: T) -> Push<T> {
+        self.add(element, self.duplicate, None, Place::Index(index))
+    }
+
+    /// Look up a value, to decide what to do with it depending on whether and where it is queued.
+    pub fn entry(&mut self, value: T) -> Entry<'_, T, S> {
+        self.purge_expired();
+
+        let hash = self.hasher.hash_one(&value);
+        match self.find_hashed(hash, &value) {
+            Some(key) => Entry::Occupied(OccupiedEntry { set: self, key }),
+            None => Entry::Vacant(VacantEntry { set: self, hash, value }),
+        }
+    }
+
+    /// Without an expiry time, a new item doesn't expire and a queued item keeps its expiry time.
+    fn add(&mut self, element: T, duplicate: DuplicatePolicy, expires: Option<Duration>, mut place: Place) -> Push<T> {
+        self.purge_expired();
+        if let Place::Index(index) = place {
+            let len = self.len();
+            assert!(index <= len, "index {} out of bounds for length {}", index, len);
+        }
+
+        let hash = self.hasher.hash_one(&element);
+        if let Some(key) = self.find_hashed(hash, &element) {
+            return match duplicate {
+                DuplicatePolicy::Ignore => Push::Ignored(element),
+                DuplicatePolicy::MoveToBack => {
+                    self.order.remove(self.values[key].position);
+                    self.compact_if_needed();
+                    if let Place::Index(index) = &mut place {
+                        *index = (*index).min(self.len());
+                    }
+                    self.place(key, place);
+                    if expires.is_some() {
+                        self.set_expiry(key, expires);
+                    }
+                    Push::MovedToBack(element)
+                }
+                DuplicatePolicy::Replace => {
+                    if expires.is_some() {
+                        self.set_expiry(key, expires);
+                    }
+                    Push::Replaced(core::mem::replace(&mut self.values[key].value, element))
+                }
+                DuplicatePolicy::Error => Push::Duplicate(element),
+            };
+        }
+
+        self.add_new(hash, element, expires, place)
+    }
+
+    /// Add an item that is known not to be in the queue, given its hash.
+    fn add_new(&mut self, hash: u64, element: T, expires: Option<Duration>, mut place: Place) -> Push<T> {
+        let mut evicted = None;
+        if let Some(limit) = self.limit.filter(|&limit| self.len() >= limit) {
+            match self.overflow {
+                OverflowPolicy::DropOldest => match self.pop() {
+                    Some(oldest) => {
+                        evicted = Some(oldest);
+                        if let Place::Index(index) = &mut place {
+                            *index = index.saturating_sub(1);
+                        }
+                    }
+                    // A limit of zero leaves nothing to evict.
+                    None => return Push::Rejected(element),
+                },
+                OverflowPolicy::Reject => return Push::Rejected(element),
+                OverflowPolicy::Panic => panic!("pushed onto a full queue with limit {}", limit),
+            }
+        }
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let Self { values, set, hasher, .. } = self;
+        let key = values.insert(Node { value: element, position: 0, seq });
+        set.insert_unique(hash, key, |&key| hasher.hash_one(&values[key].value));
+        self.seqs.insert(seq, key);
+        self.place(key, place);
+        self.set_expiry(key, expires);
+
+        match evicted {
+            Some(oldest) => Push::Evicted(oldest),
+            None => Push::Added,
+        }
     }
 
     /// Retrieve the item that has been in the queue longest.
     pub fn pop(&mut self) -> Option<T> {
-        let next = self.deq.pop_front();
+        self.purge_expired();
+
+        let key = self.order.pop_front()?;
+        Some(self.take(key))
+    }
+
+    /// Retrieve the item that has been in the queue shortest.
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.purge_expired();
 
-        if let Some(value) = next.as_ref() {
-            self.set.remove(value);
+        let key = self.order.pop_back()?;
+        Some(self.take(key))
+    }
+
+    /// Shorten the queue to `len` items, dropping the items at the back.
+    pub fn truncate(&mut self, len: usize) {
+        self.purge_expired();
+
+        while self.order.len() > len {
+            let key = self.order.pop_back().unwrap();
+            self.take(key);
+        }
+    }
+
+    /// Split the queue in two at `at`, returning the items from index `at` onwards.
+    ///
+    /// The returned queue shares the hasher and settings of this one. The items keep their sequence
+    /// numbers.
+    ///
+    /// Panics if `at` is larger than the length of the queue.
+    pub fn split_off(&mut self, at: usize) -> Self
+    where
+        S: Clone,
+    {
+        self.purge_expired();
+        let len = self.len();
+        assert!(at <= len, "index {} out of bounds for length {}", at, len);
+
+        let mut other = Self {
+            values: Slab::with_capacity(len - at),
+            order: Order::with_capacity(len - at),
+            set: HashTable::with_capacity(len - at),
+            hasher: self.hasher.clone(),
+            limit: self.limit,
+            overflow: self.overflow,
+            duplicate: self.duplicate,
+            clock: self.clock.clone(),
+            deadlines: BTreeSet::new(),
+            expiries: BTreeMap::new(),
+            seqs: BTreeMap::new(),
+            // Above the numbers of the moved items, so the numbers handed out while moving them
+            // don't collide with them.
+            next_seq: self.next_seq,
+        };
+
+        while self.order.len() > at {
+            let key = self.order.pop_back().unwrap();
+            let expires = self.expiries.get(&key).copied();
+            let seq = self.values[key].seq;
+            let element = self.take(key);
+            other.add(element, DuplicatePolicy::Ignore, expires, Place::Front);
+            // The items keep their sequence numbers.
+            let front = other.order.front().unwrap();
+            let new = core::mem::replace(&mut other.values[front].seq, seq);
+            other.seqs.remove(&new);
+            other.seqs.insert(seq, front);
+        }
+        other.next_seq = self.next_seq;
+
+        other
+    }
+
+    /// Move all items of `other` to the back of this queue, leaving `other` empty.
+    ///
+    /// Behaves like pushing the items one by one while ignoring duplicates, so items that are
+    /// already in this queue are dropped.
+    pub fn append(&mut self, other: &mut Self) {
+        other.purge_expired();
+
+        while let Some(key) = other.order.pop_front() {
+            let expires = other.expiries.get(&key).copied();
+            let element = other.take(key);
+            self.add(element, DuplicatePolicy::Ignore, expires, Place::Back);
+        }
+    }
+
+    /// Remove the items in `range` from the queue, and return them front to back.
+    ///
+    /// The items are removed right away, even if the returned iterator isn't consumed.
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<T> {
+        self.purge_expired();
+        let (start, end) = bounds(range, self.len());
+
+        let keys = self.order.range(start, end).collect::<Vec<_>>();
+        let mut drained = Vec::with_capacity(keys.len());
+        for key in keys {
+            self.order.remove(self.values[key].position);
+            drained.push(self.take(key));
         }
 
-        next
+        Drain { values: drained.into_iter() }
+    }
+
+    /// Keep only the items for which `keep` returns `true`, visiting them front to back.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut keep: F) {
+        self.extract_if(|value| !keep(value)).for_each(drop);
+    }
+
+    /// Iterate over the items for which `extract` returns `true`, removing them from the queue.
+    ///
+    /// Items are visited front to back. Items that the iterator doesn't get to before it is
+    /// dropped stay in the queue.
+    pub fn extract_if<F: FnMut(&T) -> bool>(&mut self, extract: F) -> ExtractIf<'_, T, S, F> {
+        self.purge_expired();
+
+        ExtractIf {
+            set: self,
+            index: 0,
+            extract,
+        }
     }
 
     pub fn remove(&mut self, index: usize) -> Option<T> {
-        let removed = self.deq.remove(index);
+        self.purge_expired();
+
+        let position = self.order.position(index)?;
+        let key = self.order.remove(position);
+        Some(self.take(key))
+    }
+
+    /// Remove an item from the queue, wherever it is.
+    ///
+    /// Takes logarithmic time; the index of the item doesn't need to be known.
+    pub fn remove_value<Q: ?Sized + Hash + Eq>(&mut self, x: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+    {
+        self.purge_expired();
+
+        let key = self.find(x)?;
+        self.order.remove(self.values[key].position);
+        Some(self.take(key))
+    }
+
+    /// The index of an item in the queue, that is, the number of items in front of it.
+    ///
+    /// Takes logarithmic time.
+    pub fn position<Q: ?Sized + Hash + Eq>(&self, x: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+    {
+        let key = self.find(x).filter(|&key| self.is_live(key))?;
+        Some(self.index_of(key))
+    }
+
+    /// The sequence number of a queued item.
+    ///
+    /// Every item added to the queue is numbered one higher than the item added before it, and
+    /// keeps its number while it is queued, even when it is moved. Numbers are not reused, also not
+    /// after `clear`. They increase from front to back, unless items were added other than at the
+    /// back or moved.
+    pub fn seq_of<Q: ?Sized + Hash + Eq>(&self, x: &Q) -> Option<u64>
+    where
+        T: Borrow<Q>,
+    {
+        let key = self.find(x).filter(|&key| self.is_live(key))?;
+        Some(self.values[key].seq)
+    }
+
+    /// Add an item to the queue like `push` does, and return a handle to it.
+    ///
+    /// If the item was already queued, the handle refers to the queued item. There is no handle if
+    /// the item wasn't added because the queue was full.
+    pub fn push_handle(&mut self, element: T) -> (Push<T>, Option<Handle>) {
+        let pushed = self.push(element);
+        let key = match &pushed {
+            Push::Added | Push::MovedToBack(_) | Push::Evicted(_) => self.order.back(),
+            Push::Ignored(x) | Push::Replaced(x) | Push::Duplicate(x) => self.find(x),
+            Push::Rejected(_) => None,
+        };
+        let handle = key.map(|key| Handle { key, seq: self.values[key].seq });
+
+        (pushed, handle)
+    }
+
+    /// Remove the item a handle refers to from the queue, wherever it is.
+    ///
+    /// Takes logarithmic time. Returns `None` if the handle is stale.
+    pub fn cancel(&mut self, handle: Handle) -> Option<T> {
+        self.purge_expired();
+
+        let key = self.resolve(handle)?;
+        self.order.remove(self.values[key].position);
+        Some(self.take(key))
+    }
+
+    /// Whether both queues contain the same items, regardless of their order.
+    ///
+    /// Compare with `==` to take the order into account as well.
+    pub fn set_eq<S2: BuildHasher>(&self, other: &FIFOSet<T, S2>) -> bool {
+        self.len() == other.len() && self.iter().all(|x| other.contains(x))
+    }
 
-        if let Some(value) = removed.as_ref() {
-            self.set.remove(value);
+    /// The items in `self` or `other`: first those of `self`, then those of `other` that are not
+    /// in `self`, each in queue order.
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T, S> {
+        Union {
+            iter: self.iter().chain(other.difference(self)),
+        }
+    }
+
+    /// The items in both `self` and `other`, in the order of `self`.
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> Intersection<'a, T, S> {
+        Intersection {
+            iter: self.iter(),
+            other,
+        }
+    }
+
+    /// The items in `self` but not in `other`, in the order of `self`.
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T, S> {
+        Difference {
+            iter: self.iter(),
+            other,
+        }
+    }
+
+    /// The items in exactly one of `self` and `other`: first those of `self`, then those of
+    /// `other`, each in queue order.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> SymmetricDifference<'a, T, S> {
+        SymmetricDifference {
+            iter: self.difference(other).chain(other.difference(self)),
+        }
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.len() <= other.len() && self.iter().all(|x| other.contains(x))
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        let (smaller, larger) = if self.len() <= other.len() { (self, other) } else { (other, self) };
+        smaller.iter().all(|x| !larger.contains(x))
+    }
+
+    /// Remove all expired items from the queue, and return how many there were.
+    ///
+    /// Expired items are also removed before any other modification of the queue.
+    pub fn purge_expired(&mut self) -> usize {
+        let Some(cutoff) = self.expiry_cutoff() else {
+            return 0;
+        };
+
+        let mut purged = 0;
+        while let Some(&(expires, key)) = self.deadlines.first() {
+            if expires > cutoff {
+                break;
+            }
+
+            self.order.remove(self.values[key].position);
+            self.take(key);
+            purged += 1;
         }
 
-        removed
+        purged
+    }
+
+    fn find<Q: ?Sized + Hash + Eq>(&self, x: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+    {
+        self.find_hashed(self.hasher.hash_one(x), x)
+    }
+
+    fn find_hashed<Q: ?Sized + Eq>(&self, hash: u64, x: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+    {
+        self.set.find(hash, |&key| self.values[key].value.borrow() == x).copied()
+    }
+
+    /// Remove the value stored under `key` from `values`, `set` and `seqs`.
+    ///
+    /// The caller is responsible for removing the key from `order`.
+    fn take(&mut self, key: usize) -> T {
+        let hash = self.hasher.hash_one(&self.values[key].value);
+        if let Ok(entry) = self.set.find_entry(hash, |&k| k == key) {
+            entry.remove();
+        }
+        self.seqs.remove(&self.values[key].seq);
+        self.set_expiry(key, None);
+
+        self.compact_if_needed();
+
+        self.values.remove(key).value
+    }
+
+    fn compact_if_needed(&mut self) {
+        if self.order.needs_compaction() {
+            self.order.compact();
+            self.reposition();
+        }
+    }
+
+    /// Put a key that is not in `order` at the given place.
+    fn place(&mut self, key: usize, place: Place) {
+        let place = match place {
+            Place::Index(index) if index == self.order.len() => Place::Back,
+            Place::Index(0) => Place::Front,
+            place => place,
+        };
+
+        match place {
+            Place::Back => self.values[key].position = self.order.push_back(key),
+            Place::Front => match self.order.push_front(key) {
+                Some(position) => self.values[key].position = position,
+                None => self.reposition(),
+            },
+            Place::Index(index) => {
+                self.order.insert(index, key);
+                self.reposition();
+            }
+        }
+    }
+
+    /// Update the position of every value after keys in `order` moved.
+    fn reposition(&mut self) {
+        for (position, key) in self.order.positions() {
+            self.values[key].position = position;
+        }
     }
 }
 
-impl<T: Copy + Eq + Hash> FIFOSet<T> {
-    /// Add an item to the queue.
-    pub fn push(&mut self, element: T) {
-        if self.set.insert(element) {
-            self.deq.push_back(element);
+impl<T, S> FIFOSet<T, S> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        let cutoff = self.expiry_cutoff();
+
+        Iter {
+            keys: self.order.iter(),
+            values: &self.values,
+            expiries: &self.expiries,
+            cutoff,
+            remaining: self.order.len() - self.expired_count(cutoff),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len() - self.expired_count(self.expiry_cutoff())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn now(&self) -> Duration {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            #[cfg(feature = "std")]
+            None => SystemClock.now(),
+            #[cfg(not(feature = "std"))]
+            None => panic!("a time to live needs a clock without the std feature, see set_clock"),
+        }
+    }
+
+    /// The current time, if any value has expired by then.
+    ///
+    /// Doesn't read the clock when no value has a time to live.
+    fn expiry_cutoff(&self) -> Option<Duration> {
+        let &(first, _) = self.deadlines.first()?;
+        let now = self.now();
+        (first <= now).then_some(now)
+    }
+
+    fn expired_count(&self, cutoff: Option<Duration>) -> usize {
+        cutoff.map_or(0, |cutoff| self.deadlines.range(..=(cutoff, usize::MAX)).count())
+    }
+
+    fn is_live(&self, key: usize) -> bool {
+        self.expiries.get(&key).is_none_or(|&expires| expires > self.now())
+    }
+
+    /// Whether the value stored under `key` expired at or before `cutoff`.
+    fn is_expired(&self, key: usize, cutoff: Option<Duration>) -> bool {
+        is_expired(&self.expiries, key, cutoff)
+    }
+
+    fn set_expiry(&mut self, key: usize, expires: Option<Duration>) {
+        let old = match expires {
+            Some(new) => self.expiries.insert(key, new),
+            None => self.expiries.remove(&key),
+        };
+        if let Some(old) = old {
+            self.deadlines.remove(&(old, key));
+        }
+        if let Some(new) = expires {
+            self.deadlines.insert((new, key));
+        }
+    }
+
+    /// The sequence number of the item that `pop` would return.
+    pub fn front_seq(&self) -> Option<u64> {
+        let cutoff = self.expiry_cutoff();
+        let key = self.order.iter().find(|&key| !self.is_expired(key, cutoff))?;
+        Some(self.values[key].seq)
+    }
+
+    /// The sequence number of the item that `pop_back` would return.
+    pub fn back_seq(&self) -> Option<u64> {
+        let cutoff = self.expiry_cutoff();
+        let key = self.order.iter().rev().find(|&key| !self.is_expired(key, cutoff))?;
+        Some(self.values[key].seq)
+    }
+
+    /// The queued item with the given sequence number.
+    ///
+    /// Takes logarithmic time.
+    pub fn get_by_seq(&self, seq: u64) -> Option<&T> {
+        let &key = self.seqs.get(&seq)?;
+        self.is_live(key).then(|| &self.values[key].value)
+    }
+
+    /// The item a handle refers to, or `None` if the handle is stale.
+    ///
+    /// Takes constant time.
+    pub fn get_by_handle(&self, handle: Handle) -> Option<&T> {
+        self.resolve(handle).map(|key| &self.values[key].value)
+    }
+
+    /// The index of the item a handle refers to, or `None` if the handle is stale.
+    ///
+    /// Takes logarithmic time.
+    pub fn position_by_handle(&self, handle: Handle) -> Option<usize> {
+        self.resolve(handle).map(|key| self.index_of(key))
+    }
+
+    /// The key of the live value a handle refers to.
+    fn resolve(&self, handle: Handle) -> Option<usize> {
+        let node = self.values.get(handle.key)?;
+        (node.seq == handle.seq && self.is_live(handle.key)).then_some(handle.key)
+    }
+
+    /// The index of the live value stored under `key`.
+    fn index_of(&self, key: usize) -> usize {
+        let position = self.values[key].position;
+
+        let expired_in_front = match self.expiry_cutoff() {
+            None => 0,
+            Some(cutoff) => self.deadlines.range(..=(cutoff, usize::MAX))
+                .filter(|&&(_, expired)| self.values[expired].position < position)
+                .count(),
+        };
+
+        self.order.index(position) - expired_in_front
+    }
+
+    fn nth(&self, index: usize) -> Option<&T> {
+        match self.expiry_cutoff() {
+            None => {
+                let position = self.order.position(index)?;
+                Some(&self.values[self.order.key(position)].value)
+            }
+            Some(_) => self.iter().nth(index),
+        }
+    }
+}
+
+/// Whether the value stored under `key` expired at or before `cutoff`, given the expiry times of
+/// the values by key.
+pub(crate) fn is_expired(expiries: &BTreeMap<usize, Duration>, key: usize, cutoff: Option<Duration>) -> bool {
+    match cutoff {
+        Some(cutoff) => expiries.get(&key).is_some_and(|&expires| expires <= cutoff),
+        None => false,
+    }
+}
+
+/// Resolve a range of indices into a queue of length `len`, panicking if it is out of bounds.
+fn bounds<R: RangeBounds<usize>>(range: R, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "range starts at {} but ends at {}", start, end);
+    assert!(end <= len, "range end {} out of bounds for length {}", end, len);
+
+    (start, end)
+}
+
+impl<T, S: Default> Default for FIFOSet<T, S> {
+    fn default() -> Self {
+        Self {
+            values: Slab::new(),
+            order: Order::default(),
+            set: HashTable::new(),
+            hasher: S::default(),
+            limit: None,
+            overflow: OverflowPolicy::Panic,
+            duplicate: DuplicatePolicy::Ignore,
+            clock: None,
+            deadlines: BTreeSet::new(),
+            expiries: BTreeMap::new(),
+            seqs: BTreeMap::new(),
+            next_seq: 0,
         }
     }
 }
 
-impl<T> Index<usize> for FIFOSet<T> {
+impl<T, S> Index<usize> for FIFOSet<T, S> {
     type Output = T;
 
     fn index(&self, index: usize) -> &Self::Output {
-        self.deq.index(index)
+        self.nth(index).expect("index out of bounds")
     }
 }
 
-impl<A: Copy + Eq + Hash> FromIterator<A> for FIFOSet<A> {
+impl<A: Eq + Hash, S: BuildHasher + Default> FromIterator<A> for FIFOSet<A, S> {
     fn from_iter<T: IntoIterator<Item=A>>(iter: T) -> Self {
         let iterator = iter.into_iter();
         let (lower, _) = iterator.size_hint();
-        let mut deq = FIFOSet::with_capacity(lower);
+        let mut deq = FIFOSet::with_capacity_and_hasher(lower, S::default());
         deq.extend(iterator);
         deq
     }
 }
 
-impl<A: Copy + Eq + Hash> Extend<A> for FIFOSet<A> {
+impl<A: Eq + Hash, S: BuildHasher> Extend<A> for FIFOSet<A, S> {
     fn extend<T: IntoIterator<Item=A>>(&mut self, iter: T) {
         for item in iter.into_iter() {
             self.push(item);
         }
     }
 }
+
+impl<'a, A: Copy + Eq + Hash + 'a, S: BuildHasher> Extend<&'a A> for FIFOSet<A, S> {
+    fn extend<T: IntoIterator<Item=&'a A>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher + Default> From<Vec<T>> for FIFOSet<T, S> {
+    /// Keeps the first of any duplicates.
+    fn from(values: Vec<T>) -> Self {
+        values.into_iter().collect()
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher + Default, const N: usize> From<[T; N]> for FIFOSet<T, S> {
+    /// Keeps the first of any duplicates.
+    fn from(values: [T; N]) -> Self {
+        values.into_iter().collect()
+    }
+}
+
+impl<T, S> From<FIFOSet<T, S>> for VecDeque<T> {
+    fn from(set: FIFOSet<T, S>) -> Self {
+        set.into_iter().collect()
+    }
+}
+
+impl<T, S> From<FIFOSet<T, S>> for Vec<T> {
+    fn from(set: FIFOSet<T, S>) -> Self {
+        set.into_iter().collect()
+    }
+}
+
+impl<T, S> IntoIterator for FIFOSet<T, S> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let cutoff = self.expiry_cutoff();
+        let keys = self.order.iter()
+            .filter(|&key| !self.is_expired(key, cutoff))
+            .collect::<Vec<_>>();
+
+        IntoIter {
+            keys: keys.into_iter(),
+            values: self.values,
+        }
+    }
+}
+
+impl<'a, T, S> IntoIterator for &'a FIFOSet<T, S> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Items are only handed out by shared reference, because changing them could change their hash.
+impl<'a, T, S> IntoIterator for &'a mut FIFOSet<T, S> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: fmt::Debug, S> fmt::Debug for FIFOSet<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Queues are equal when they hold equal items in the same order. See `FIFOSet::set_eq` for
+/// comparing regardless of order.
+impl<T: PartialEq, S> PartialEq for FIFOSet<T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq, S> Eq for FIFOSet<T, S> {}
+
+impl<T: Hash, S> Hash for FIFOSet<T, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        self.iter().for_each(|x| x.hash(state));
+    }
+}
+
+/// Queues are compared lexicographically, front to back.
+impl<T: PartialOrd, S> PartialOrd for FIFOSet<T, S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Ord, S> Ord for FIFOSet<T, S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{DuplicatePolicy, FIFOSet, ManualClock, OverflowPolicy, Push};
+
+    const TTL: Duration = Duration::from_secs(10);
+
+    /// A queue of `0..10` in which the odd values and 0 expire after `TTL`, and the clock that
+    /// expires them.
+    fn expiring() -> (FIFOSet<u32>, ManualClock) {
+        let clock = ManualClock::new();
+        let mut set = FIFOSet::new();
+        set.set_clock(clock.clone());
+        for value in 0..10 {
+            if value % 2 == 1 || value == 0 {
+                set.push_with_ttl(value, TTL);
+            } else {
+                set.push(value);
+            }
+        }
+
+        (set, clock)
+    }
+
+    #[test]
+    fn expired_values_are_invisible() {
+        let (set, clock) = expiring();
+        assert_eq!(set.len(), 10);
+        assert_eq!(set.peek(), Some(&0));
+
+        clock.advance(TTL);
+        assert_eq!(set.len(), 4);
+        assert!(!set.is_empty());
+        assert_eq!(set.iter().copied().collect::<Vec<_>>(), [2, 4, 6, 8]);
+        assert_eq!(set.iter().rev().copied().collect::<Vec<_>>(), [8, 6, 4, 2]);
+        assert_eq!(set.iter().len(), 4);
+        assert_eq!(set.peek(), Some(&2));
+        assert_eq!(set.get(1), Some(&4));
+        assert_eq!(set.get(4), None);
+        assert_eq!(set[3], 8);
+        assert!(set.contains(&2));
+        assert!(!set.contains(&3));
+        assert_eq!(set.get_value(&3), None);
+        assert_eq!(set.position(&6), Some(2));
+        assert_eq!(set.position(&5), None);
+        assert_eq!(Vec::from(set), [2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn range_skips_expired_values() {
+        let (set, clock) = expiring();
+        clock.advance(TTL);
+
+        assert_eq!(set.range(..).copied().collect::<Vec<_>>(), [2, 4, 6, 8]);
+        assert_eq!(set.range(1..3).copied().collect::<Vec<_>>(), [4, 6]);
+        assert_eq!(set.range(1..=3).rev().copied().collect::<Vec<_>>(), [8, 6, 4]);
+        assert_eq!(set.range(2..2).count(), 0);
+        assert_eq!(set.range(4..).count(), 0);
+        assert_eq!(set.range(..1).len(), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn range_out_of_bounds() {
+        let (set, clock) = expiring();
+        clock.advance(TTL);
+        set.range(..5);
+    }
+
+    #[test]
+    fn purge_expired() {
+        let (mut set, clock) = expiring();
+        assert_eq!(set.purge_expired(), 0);
+
+        clock.advance(TTL - Duration::from_nanos(1));
+        assert_eq!(set.len(), 10);
+        assert_eq!(set.purge_expired(), 0);
+
+        clock.advance(Duration::from_nanos(1));
+        assert_eq!(set.purge_expired(), 6);
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.purge_expired(), 0);
+
+        // Expired values are purged before a modification, so they don't count as duplicates.
+        let (mut set, clock) = expiring();
+        clock.advance(TTL);
+        assert_eq!(set.push(3), Push::Added);
+        assert_eq!(set.iter().copied().collect::<Vec<_>>(), [2, 4, 6, 8, 3]);
+        assert_eq!(set.pop(), Some(2));
+    }
+
+    #[test]
+    fn duplicate_keeps_time_to_live() {
+        let (mut set, clock) = expiring();
+        set.set_duplicate_policy(DuplicatePolicy::MoveToBack);
+        assert_eq!(set.push(1), Push::MovedToBack(1));
+        set.set_duplicate_policy(DuplicatePolicy::Replace);
+        assert_eq!(set.push(3), Push::Replaced(3));
+        assert_eq!(set.push_with_ttl(4, TTL), Push::Replaced(4));
+
+        clock.advance(TTL);
+        assert_eq!(set.iter().copied().collect::<Vec<_>>(), [2, 6, 8]);
+    }
+
+    #[test]
+    fn duplicate_takes_new_time_to_live() {
+        let (mut set, clock) = expiring();
+        set.set_duplicate_policy(DuplicatePolicy::MoveToBack);
+        clock.advance(TTL / 2);
+        assert_eq!(set.push_with_ttl(1, TTL), Push::MovedToBack(1));
+
+        clock.advance(TTL / 2);
+        assert_eq!(set.iter().copied().collect::<Vec<_>>(), [2, 4, 6, 8, 1]);
+        clock.advance(TTL / 2);
+        assert_eq!(set.iter().copied().collect::<Vec<_>>(), [2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn unrepresentable_deadline_never_passes() {
+        let clock = ManualClock::new();
+        let mut set = FIFOSet::new();
+        set.set_clock(clock.clone());
+        clock.advance(TTL);
+        set.push_with_ttl(1, Duration::MAX);
+
+        clock.advance(Duration::from_secs(u32::MAX.into()));
+        assert_eq!(set.pop(), Some(1));
+    }
+
+    #[test]
+    fn sequence_numbers() {
+        let mut set = FIFOSet::new();
+        assert_eq!(set.front_seq(), None);
+        assert_eq!(set.back_seq(), None);
+
+        for value in 0..5 {
+            set.push(value);
+        }
+        set.push_front(5);
+        assert_eq!(set.seq_of(&5), Some(5));
+        assert_eq!(set.front_seq(), Some(5));
+        assert_eq!(set.back_seq(), Some(4));
+
+        assert_eq!(set.pop(), Some(5));
+        assert_eq!(set.get_by_seq(5), None);
+        assert_eq!(set.front_seq(), Some(0));
+        assert_eq!(set.get_by_seq(3), Some(&3));
+        assert_eq!(set.get_by_seq(6), None);
+
+        // Numbers aren't reused.
+        set.clear();
+        set.push(0);
+        assert_eq!(set.seq_of(&0), Some(6));
+        assert_eq!(set.get_by_seq(0), None);
+        assert_eq!(set.get_by_seq(6), Some(&0));
+    }
+
+    #[test]
+    fn expired_sequence_numbers() {
+        let (set, clock) = expiring();
+        clock.advance(TTL);
+
+        assert_eq!(set.front_seq(), Some(2));
+        assert_eq!(set.back_seq(), Some(8));
+        assert_eq!(set.get_by_seq(1), None);
+        assert_eq!(set.seq_of(&1), None);
+        assert_eq!(set.get_by_seq(2), Some(&2));
+    }
+
+    #[test]
+    fn split_off_keeps_sequence_numbers() {
+        let mut set = (0..6).collect::<FIFOSet<u32>>();
+        set.push_front(6);
+        let seqs = set.iter().map(|x| set.seq_of(x).unwrap()).collect::<Vec<_>>();
+
+        let mut other = set.split_off(3);
+        let split = set.iter().map(|x| set.seq_of(x).unwrap())
+            .chain(other.iter().map(|x| other.seq_of(x).unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(split, seqs);
+        assert_eq!(other.front_seq(), Some(2));
+        assert_eq!(other.back_seq(), Some(5));
+        assert_eq!(other.get_by_seq(3), Some(&3));
+        assert_eq!(other.get_by_seq(1), None);
+        assert_eq!(set.get_by_seq(3), None);
+
+        // Both queues continue numbering where the original queue was.
+        set.push(7);
+        other.push(8);
+        assert_eq!(set.seq_of(&7), Some(7));
+        assert_eq!(other.seq_of(&8), Some(7));
+        assert_eq!(other.get_by_seq(7), Some(&8));
+    }
+
+    #[test]
+    fn handles() {
+        let mut set = FIFOSet::new();
+        let (pushed, first) = set.push_handle(1);
+        assert_eq!(pushed, Push::Added);
+        let first = first.unwrap();
+        let (_, second) = set.push_handle(2);
+        let second = second.unwrap();
+        set.push_front(0);
+
+        assert_eq!(set.get_by_handle(first), Some(&1));
+        assert_eq!(set.position_by_handle(first), Some(1));
+        assert_eq!(set.position_by_handle(second), Some(2));
+
+        assert_eq!(set.pop(), Some(0));
+        assert_eq!(set.position_by_handle(second), Some(1));
+        assert_eq!(set.pop(), Some(1));
+        assert_eq!(set.get_by_handle(first), None);
+        assert_eq!(set.position_by_handle(first), None);
+        assert_eq!(set.cancel(first), None);
+
+        assert_eq!(set.cancel(second), Some(2));
+        assert_eq!(set.cancel(second), None);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn handle_to_reused_key() {
+        let mut set = FIFOSet::new();
+        let (_, old) = set.push_handle(1);
+        let old = old.unwrap();
+        set.pop();
+
+        // The new value is stored in the place of the old one, but the old handle stays stale.
+        let (_, new) = set.push_handle(2);
+        let new = new.unwrap();
+        assert_eq!(old.key, new.key);
+        assert_ne!(old, new);
+        assert_eq!(set.get_by_handle(old), None);
+        assert_eq!(set.cancel(old), None);
+        assert_eq!(set.get_by_handle(new), Some(&2));
+    }
+
+    #[test]
+    fn handle_to_expired_value() {
+        let (mut set, clock) = expiring();
+        let (_, handle) = set.push_handle(10);
+        let handle = handle.unwrap();
+        let (_, expiring) = set.push_handle(1);
+        let expiring = expiring.unwrap();
+        assert_eq!(set.position_by_handle(handle), Some(10));
+
+        clock.advance(TTL);
+        assert_eq!(set.get_by_handle(expiring), None);
+        assert_eq!(set.position_by_handle(expiring), None);
+        assert_eq!(set.position_by_handle(handle), Some(4));
+        assert_eq!(set.cancel(expiring), None);
+        assert_eq!(set.cancel(handle), Some(10));
+    }
+
+    #[test]
+    fn handle_to_duplicate() {
+        let mut set = FIFOSet::new();
+        let (_, queued) = set.push_handle(1);
+        let queued = queued.unwrap();
+        set.push(2);
+
+        let (pushed, handle) = set.push_handle(1);
+        assert_eq!(pushed, Push::Ignored(1));
+        assert_eq!(handle, Some(queued));
+
+        set.set_duplicate_policy(DuplicatePolicy::Replace);
+        let (pushed, handle) = set.push_handle(1);
+        assert_eq!(pushed, Push::Replaced(1));
+        assert_eq!(handle, Some(queued));
+
+        set.set_duplicate_policy(DuplicatePolicy::MoveToBack);
+        let (pushed, handle) = set.push_handle(1);
+        assert_eq!(pushed, Push::MovedToBack(1));
+        assert_eq!(handle, Some(queued));
+        assert_eq!(set.position_by_handle(queued), Some(1));
+    }
+
+    #[test]
+    fn handle_when_full() {
+        let mut set = FIFOSet::bounded(1, OverflowPolicy::Reject);
+        set.push(1);
+        assert_eq!(set.push_handle(2), (Push::Rejected(2), None));
+
+        let mut set = FIFOSet::bounded(1, OverflowPolicy::DropOldest);
+        let (_, first_evicted) = set.push_handle(1);
+        let (pushed, second) = set.push_handle(2);
+        assert_eq!(pushed, Push::Evicted(1));
+        assert_eq!(set.get_by_handle(first_evicted.unwrap()), None);
+        assert_eq!(set.get_by_handle(second.unwrap()), Some(&2));
+    }
+}
@@ -0,0 +1,301 @@
+//! A queue of unique keys, each with a value.
+
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::iter::{FromIterator, FusedIterator};
+
+use crate::{DefaultHashBuilder, FIFOSet, OverflowPolicy, Push};
+
+/// A `FIFOSet` of keys that each carry a value.
+///
+/// Keys are unique and queued in the order they were pushed, like the values of a `FIFOSet`. The
+/// value of a queued key can be read and changed without moving the key.
+#[derive(Clone)]
+pub struct FIFOMap<K, V, S = DefaultHashBuilder> {
+    set: FIFOSet<Bucket<K, V>, S>,
+}
+
+/// A key and its value, hashed and compared by the key only.
+#[derive(Clone)]
+struct Bucket<K, V> {
+    key: K,
+    value: V,
+}
+
+#[cfg(feature = "std")]
+impl<K: Eq + Hash, V> FIFOMap<K, V> {
+    pub fn new() -> Self {
+        Self { set: FIFOSet::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { set: FIFOSet::with_capacity(capacity) }
+    }
+
+    /// Create an empty map that holds at most `limit` keys.
+    pub fn bounded(limit: usize, overflow: OverflowPolicy) -> Self {
+        Self { set: FIFOSet::bounded(limit, overflow) }
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> FIFOMap<K, V, S> {
+    /// Create an empty map which will use the given hash builder to hash keys.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self { set: FIFOSet::with_hasher(hasher) }
+    }
+
+    /// Create an empty map with space for at least `capacity` keys, which will use the given
+    /// hash builder to hash keys.
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self { set: FIFOSet::with_capacity_and_hasher(capacity, hasher) }
+    }
+
+    /// Create an empty map that holds at most `limit` keys, which will use the given hash
+    /// builder to hash keys.
+    pub fn bounded_with_hasher(limit: usize, overflow: OverflowPolicy, hasher: S) -> Self {
+        Self { set: FIFOSet::bounded_with_hasher(limit, overflow, hasher) }
+    }
+
+    /// Add a key with its value to the back of the queue.
+    ///
+    /// A key that is already queued is left as it is, along with its value, and the pushed key
+    /// and value are returned in `Push::Ignored`. If the map is full, the overflow policy decides
+    /// what happens.
+    pub fn push(&mut self, key: K, value: V) -> Push<(K, V)> {
+        match self.set.push(Bucket { key, value }) {
+            Push::Added => Push::Added,
+            Push::Ignored(bucket) => Push::Ignored(bucket.into_pair()),
+            Push::MovedToBack(bucket) => Push::MovedToBack(bucket.into_pair()),
+            Push::Replaced(bucket) => Push::Replaced(bucket.into_pair()),
+            Push::Duplicate(bucket) => Push::Duplicate(bucket.into_pair()),
+            Push::Evicted(bucket) => Push::Evicted(bucket.into_pair()),
+            Push::Rejected(bucket) => Push::Rejected(bucket.into_pair()),
+        }
+    }
+
+    /// Retrieve the key that has been in the queue longest, with its value.
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        self.set.pop().map(Bucket::into_pair)
+    }
+
+    /// The key that `pop` would return, with its value.
+    pub fn peek(&self) -> Option<(&K, &V)> {
+        self.set.peek().map(Bucket::as_pair)
+    }
+
+    pub fn get<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        let slot = self.find(key)?;
+        Some(&self.set.values[slot].value.value)
+    }
+
+    pub fn get_mut<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        let slot = self.find(key)?;
+        Some(&mut self.set.values[slot].value.value)
+    }
+
+    /// Replace the value of a queued key, without moving the key.
+    ///
+    /// Returns the old value, or `None` if the key isn't queued, in which case the map is left
+    /// as it is.
+    pub fn update<Q: ?Sized + Hash + Eq>(&mut self, key: &Q, value: V) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        self.get_mut(key).map(|old| core::mem::replace(old, value))
+    }
+
+    /// Remove a key from the queue, wherever it is, with its value.
+    pub fn remove<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+    {
+        let slot = self.find(key)?;
+        self.set.order.remove(self.set.values[slot].position);
+        Some(self.set.take(slot).into_pair())
+    }
+
+    pub fn contains_key<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.find(key).is_some()
+    }
+
+    /// The index of a key in the queue, that is, the number of keys in front of it.
+    pub fn position<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+    {
+        let slot = self.find(key)?;
+        Some(self.set.order.index(self.set.values[slot].position))
+    }
+
+    pub fn clear(&mut self) {
+        self.set.clear();
+    }
+
+    /// The slab key of the bucket with the given key.
+    ///
+    /// Keys never expire, so the result doesn't need to be checked for expiry.
+    fn find<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+    {
+        // A bucket hashes like its key, so hashing the borrowed key finds it.
+        let FIFOSet { values, set, hasher, .. } = &self.set;
+        set.find(hasher.hash_one(key), |&slot| values[slot].value.key.borrow() == key).copied()
+    }
+}
+
+impl<K, V, S> FIFOMap<K, V, S> {
+    /// An iterator over the keys and their values, front to back.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { iter: self.set.iter() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+}
+
+impl<K, V> Bucket<K, V> {
+    fn into_pair(self) -> (K, V) {
+        (self.key, self.value)
+    }
+
+    fn as_pair(&self) -> (&K, &V) {
+        (&self.key, &self.value)
+    }
+}
+
+impl<K: Hash, V> Hash for Bucket<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl<K: PartialEq, V> PartialEq for Bucket<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: Eq, V> Eq for Bucket<K, V> {}
+
+impl<K, V, S: Default> Default for FIFOMap<K, V, S> {
+    fn default() -> Self {
+        Self { set: FIFOSet::default() }
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher + Default> FromIterator<(K, V)> for FIFOMap<K, V, S> {
+    /// Keeps the first of any duplicate keys, with its value.
+    fn from_iter<T: IntoIterator<Item=(K, V)>>(iter: T) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> Extend<(K, V)> for FIFOMap<K, V, S> {
+    fn extend<T: IntoIterator<Item=(K, V)>>(&mut self, iter: T) {
+        for (key, value) in iter {
+            self.push(key, value);
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for FIFOMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S> IntoIterator for FIFOMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { iter: self.set.into_iter() }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a FIFOMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the keys of a `FIFOMap` and their values, front to back.
+pub struct Iter<'a, K, V> {
+    iter: crate::Iter<'a, Bucket<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(Bucket::as_pair)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(Bucket::as_pair)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self { iter: self.iter.clone() }
+    }
+}
+
+/// An owning iterator over the keys of a `FIFOMap` and their values, front to back.
+pub struct IntoIter<K, V> {
+    iter: crate::IntoIter<Bucket<K, V>>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(Bucket::into_pair)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(Bucket::into_pair)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
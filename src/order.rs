@@ -0,0 +1,491 @@
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+use core::slice;
+
+/// Marks a position in `Order` whose key has been removed.
+const HOLE: usize = usize::MAX;
+
+/// The queue order, as a sequence of slab keys.
+///
+/// Removing a key anywhere in the sequence leaves a hole behind, so that the positions of all
+/// other keys stay valid. A Fenwick tree over the occupied positions translates between positions
+/// and indices, that is, the number of keys in front of a position. Once holes outnumber the keys,
+/// the sequence should be compacted, which moves every key to a new position.
+///
+/// The sequence never ends in a hole. Holes in front of the first key are reused by `push_front`.
+#[derive(Clone, Default)]
+pub(crate) struct Order {
+    keys: Vec<usize>,
+    counts: Fenwick,
+    /// Position of the first key, or `keys.len()` if there are none.
+    head: usize,
+    len: usize,
+}
+
+impl Order {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            keys: Vec::with_capacity(capacity),
+            counts: Fenwick::with_capacity(capacity),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.keys.capacity()
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.keys.reserve(additional);
+        self.counts.reserve(additional);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.keys.clear();
+        self.counts.clear();
+        self.head = 0;
+        self.len = 0;
+    }
+
+    fn has_holes(&self) -> bool {
+        self.keys.len() - self.head != self.len
+    }
+
+    /// The key at `position`, if that position isn't a hole.
+    pub(crate) fn key(&self, position: usize) -> usize {
+        debug_assert_ne!(self.keys[position], HOLE);
+        self.keys[position]
+    }
+
+    /// The position of the key with the given index.
+    pub(crate) fn position(&self, index: usize) -> Option<usize> {
+        if index >= self.len {
+            None
+        } else if self.has_holes() {
+            Some(self.counts.select(index))
+        } else {
+            Some(self.head + index)
+        }
+    }
+
+    /// The index of the key at `position`.
+    pub(crate) fn index(&self, position: usize) -> usize {
+        if self.has_holes() {
+            self.counts.prefix(position)
+        } else {
+            position - self.head
+        }
+    }
+
+    pub(crate) fn front(&self) -> Option<usize> {
+        self.keys.get(self.head).copied()
+    }
+
+    pub(crate) fn back(&self) -> Option<usize> {
+        if self.len == 0 { None } else { self.keys.last().copied() }
+    }
+
+    /// Append a key and return its position.
+    pub(crate) fn push_back(&mut self, key: usize) -> usize {
+        let position = self.keys.len();
+        self.keys.push(key);
+        self.counts.push(1);
+        self.len += 1;
+        position
+    }
+
+    /// Prepend a key and return its position, or `None` if all keys had to be moved to make room.
+    pub(crate) fn push_front(&mut self, key: usize) -> Option<usize> {
+        let moved = self.head == 0;
+        if moved {
+            // Leave room for more keys in front, proportional to the number of keys, so that
+            // moving them is amortized.
+            let room = self.len / 2 + 1;
+            self.keys.retain(|&key| key != HOLE);
+            self.keys.splice(0..0, core::iter::repeat_n(HOLE, room));
+            self.counts.rebuild(self.keys.iter().map(|&key| key != HOLE));
+            self.head = room;
+        }
+
+        self.head -= 1;
+        self.keys[self.head] = key;
+        self.counts.increment(self.head);
+        self.len += 1;
+
+        (!moved).then_some(self.head)
+    }
+
+    /// Insert a key so that it gets the given index. All keys behind it move to a new position.
+    pub(crate) fn insert(&mut self, index: usize, key: usize) {
+        let position = self.position(index).unwrap_or(self.keys.len());
+        self.keys.insert(position, key);
+        self.counts.rebuild(self.keys.iter().map(|&key| key != HOLE));
+        self.head = self.head.min(position);
+        self.len += 1;
+    }
+
+    pub(crate) fn pop_front(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.remove(self.head))
+        }
+    }
+
+    pub(crate) fn pop_back(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.remove(self.keys.len() - 1))
+        }
+    }
+
+    /// Remove the key at `position`, leaving a hole, and return the key.
+    pub(crate) fn remove(&mut self, position: usize) -> usize {
+        let key = core::mem::replace(&mut self.keys[position], HOLE);
+        debug_assert_ne!(key, HOLE);
+        self.counts.decrement(position);
+        self.len -= 1;
+
+        while self.head < self.keys.len() && self.keys[self.head] == HOLE {
+            self.head += 1;
+        }
+        while self.keys.last() == Some(&HOLE) {
+            self.keys.pop();
+            self.counts.pop();
+        }
+        self.head = self.head.min(self.keys.len());
+
+        key
+    }
+
+    pub(crate) fn swap(&mut self, i: usize, j: usize) {
+        self.keys.swap(i, j);
+    }
+
+    pub(crate) fn needs_compaction(&self) -> bool {
+        let holes = self.keys.len() - self.len;
+        holes > 16 && holes > self.len
+    }
+
+    /// Remove all holes. Afterwards, the position of each key equals its index.
+    pub(crate) fn compact(&mut self) {
+        self.keys.retain(|&key| key != HOLE);
+        self.counts.rebuild(self.keys.iter().map(|_| true));
+        self.head = 0;
+    }
+
+    /// The position and key of every key, front to back.
+    pub(crate) fn positions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.keys.iter()
+            .enumerate()
+            .skip(self.head)
+            .filter(|&(_, &key)| key != HOLE)
+            .map(|(position, &key)| (position, key))
+    }
+
+    /// Iterate over the keys with index in `start..end`.
+    pub(crate) fn range(&self, start: usize, end: usize) -> Keys<'_> {
+        assert!(start <= end, "range starts at {} but ends at {}", start, end);
+        assert!(end <= self.len, "range end {} out of bounds for length {}", end, self.len);
+
+        let first = self.position(start).unwrap_or(self.keys.len());
+        let last = self.position(end).unwrap_or(self.keys.len());
+
+        Keys {
+            keys: self.keys[first..last].iter(),
+            remaining: end - start,
+        }
+    }
+
+    pub(crate) fn iter(&self) -> Keys<'_> {
+        Keys {
+            keys: self.keys[self.head..].iter(),
+            remaining: self.len,
+        }
+    }
+}
+
+/// An iterator over the keys in an `Order`, skipping holes.
+#[derive(Clone)]
+pub(crate) struct Keys<'a> {
+    keys: slice::Iter<'a, usize>,
+    remaining: usize,
+}
+
+impl Iterator for Keys<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let key = *self.keys.find(|&&key| key != HOLE)?;
+        self.remaining -= 1;
+        Some(key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl DoubleEndedIterator for Keys<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let key = *self.keys.rfind(|&&key| key != HOLE)?;
+        self.remaining -= 1;
+        Some(key)
+    }
+}
+
+impl ExactSizeIterator for Keys<'_> {}
+
+impl FusedIterator for Keys<'_> {}
+
+/// A Fenwick tree over zeros and ones, supporting prefix counts and finding the n-th one.
+#[derive(Clone, Default)]
+struct Fenwick {
+    tree: Vec<usize>,
+}
+
+impl Fenwick {
+    fn with_capacity(capacity: usize) -> Self {
+        Self { tree: Vec::with_capacity(capacity) }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.tree.reserve(additional);
+    }
+
+    fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    /// Replace the contents, in linear time.
+    fn rebuild(&mut self, ones: impl Iterator<Item = bool>) {
+        self.tree.clear();
+        self.tree.extend(ones.map(usize::from));
+        for i in 0..self.tree.len() {
+            let parent = i | (i + 1);
+            if parent < self.tree.len() {
+                self.tree[parent] += self.tree[i];
+            }
+        }
+    }
+
+    fn push(&mut self, value: usize) {
+        let i = self.tree.len();
+        let covered = (i + 1) & (i + 1).wrapping_neg();
+        let sum = self.prefix(i) - self.prefix(i + 1 - covered);
+        self.tree.push(sum + value);
+    }
+
+    /// Remove the last value.
+    fn pop(&mut self) {
+        self.tree.pop();
+    }
+
+    fn increment(&mut self, mut i: usize) {
+        while i < self.tree.len() {
+            self.tree[i] += 1;
+            i |= i + 1;
+        }
+    }
+
+    fn decrement(&mut self, mut i: usize) {
+        while i < self.tree.len() {
+            self.tree[i] -= 1;
+            i |= i + 1;
+        }
+    }
+
+    /// Sum of the values before index `i`.
+    fn prefix(&self, mut i: usize) -> usize {
+        let mut sum = 0;
+        while i > 0 {
+            sum += self.tree[i - 1];
+            i &= i - 1;
+        }
+        sum
+    }
+
+    /// Index of the `n`-th one, counting from zero.
+    fn select(&self, mut n: usize) -> usize {
+        let mut i = 0;
+        let mut step = (self.tree.len() + 1).next_power_of_two() / 2;
+        while step > 0 {
+            if i + step <= self.tree.len() && self.tree[i + step - 1] <= n {
+                i += step;
+                n -= self.tree[i - 1];
+            }
+            step /= 2;
+        }
+        i
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::VecDeque;
+    use alloc::vec::Vec;
+
+    use super::{Order, HOLE};
+
+    /// Check every index and position against the keys in the order they should be.
+    fn check(order: &Order, expected: &VecDeque<usize>) {
+        assert_eq!(order.len(), expected.len());
+        assert_eq!(order.iter().collect::<Vec<_>>(), Vec::from(expected.clone()));
+        let reversed = expected.iter().rev().copied().collect::<Vec<_>>();
+        assert_eq!(order.iter().rev().collect::<Vec<_>>(), reversed);
+        assert_ne!(order.keys.last(), Some(&HOLE));
+
+        for (index, &key) in expected.iter().enumerate() {
+            let position = order.position(index).unwrap();
+            assert_eq!(order.key(position), key);
+            assert_eq!(order.index(position), index);
+        }
+        assert_eq!(order.position(expected.len()), None);
+
+        for (position, key) in order.positions() {
+            assert_eq!(order.key(position), key);
+        }
+    }
+
+    #[test]
+    fn push_and_remove() {
+        let mut order = Order::default();
+        let mut expected = VecDeque::new();
+        for key in 0..10 {
+            assert_eq!(order.push_back(key), key);
+            expected.push_back(key);
+        }
+
+        // Holes in the middle, at the front and at the back.
+        order.remove(order.position(4).unwrap());
+        expected.remove(4);
+        check(&order, &expected);
+        assert_eq!(order.pop_front(), expected.pop_front());
+        check(&order, &expected);
+        assert_eq!(order.pop_back(), expected.pop_back());
+        check(&order, &expected);
+
+        order.insert(2, 10);
+        expected.insert(2, 10);
+        check(&order, &expected);
+
+        order.swap(order.position(0).unwrap(), order.position(3).unwrap());
+        expected.swap(0, 3);
+        check(&order, &expected);
+
+        while let Some(key) = expected.pop_front() {
+            assert_eq!(order.pop_front(), Some(key));
+            check(&order, &expected);
+        }
+        assert_eq!(order.front(), None);
+        assert_eq!(order.back(), None);
+    }
+
+    #[test]
+    fn push_front_reuses_room() {
+        let mut order = Order::default();
+        for key in 0..6 {
+            order.push_back(key);
+        }
+
+        // The first push to the front moves all keys and makes room for `len / 2 + 1` keys, of
+        // which it takes one.
+        assert_eq!(order.push_front(6), None);
+        assert_eq!(order.head, 3);
+        for key in 7..10 {
+            assert_eq!(order.push_front(key), Some(order.head));
+        }
+        assert_eq!(order.head, 0);
+        assert_eq!(order.push_front(10), None);
+
+        check(&order, &[10, 9, 8, 7, 6, 0, 1, 2, 3, 4, 5].into_iter().collect());
+
+        // Popping from the front leaves room that is taken again without moving.
+        let head = order.head;
+        assert_eq!(order.pop_front(), Some(10));
+        assert_eq!(order.push_front(11), Some(head));
+    }
+
+    #[test]
+    fn compaction() {
+        let mut order = Order::default();
+        let mut expected = VecDeque::new();
+        for key in 0..100 {
+            order.push_back(key);
+            expected.push_back(key);
+        }
+
+        // Remove every other key from the back half, which leaves holes that can't be trimmed.
+        for index in 50..75 {
+            order.remove(order.position(index).unwrap());
+            expected.remove(index);
+        }
+        assert!(!order.needs_compaction());
+        for _ in 0..40 {
+            order.remove(order.position(0).unwrap());
+            expected.pop_front();
+        }
+        check(&order, &expected);
+        assert!(order.needs_compaction());
+
+        order.compact();
+        assert!(!order.has_holes());
+        check(&order, &expected);
+        for (index, (position, _)) in order.positions().enumerate() {
+            assert_eq!(position, index);
+        }
+    }
+
+    #[test]
+    fn random_operations() {
+        let mut order = Order::default();
+        let mut expected = VecDeque::new();
+        let mut state = 1u64;
+        let mut random = |n: usize| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) as usize % n
+        };
+
+        for key in 0..2000 {
+            match random(6) {
+                0 | 1 => {
+                    order.push_back(key);
+                    expected.push_back(key);
+                }
+                2 => {
+                    order.push_front(key);
+                    expected.push_front(key);
+                }
+                3 => {
+                    let index = random(expected.len() + 1);
+                    order.insert(index, key);
+                    expected.insert(index, key);
+                }
+                _ if !expected.is_empty() => {
+                    let index = random(expected.len());
+                    let position = order.position(index).unwrap();
+                    assert_eq!(order.remove(position), expected.remove(index).unwrap());
+                }
+                _ => {}
+            }
+            if order.needs_compaction() {
+                order.compact();
+            }
+            check(&order, &expected);
+        }
+    }
+}
@@ -0,0 +1,600 @@
+use std::fs::{self, File, OpenOptions};
+use std::hash::Hash;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use crate::{FIFOSet, Push};
+
+/// Converts values to bytes and back, for storing them in a `PersistentFIFOSet`.
+pub trait Codec<T> {
+    /// Append the encoding of `value` to `out`.
+    fn encode(&self, value: &T, out: &mut Vec<u8>);
+
+    /// Decode a value from the bytes written by `encode`.
+    ///
+    /// Bytes that don't encode a value should result in an error of kind `InvalidData`.
+    fn decode(&self, bytes: &[u8]) -> io::Result<T>;
+}
+
+/// A `FIFOSet` that survives restarts by storing its contents in a directory.
+///
+/// Every modification is appended to a log before it is applied. Once the log has grown large
+/// compared to the queue, the queue is written to a snapshot and the log starts over. Opening the
+/// directory again replays the log on top of the snapshot. If the last record in the log was only
+/// partially written, it is discarded.
+///
+/// Records reach the operating system before a modification returns, so they survive the process
+/// crashing. Call `sync` to make sure they also survive the machine crashing.
+///
+/// Read access goes through `Deref` to the queue.
+pub struct PersistentFIFOSet<T, C> {
+    set: FIFOSet<T>,
+    codec: C,
+    dir: PathBuf,
+    log: File,
+    /// Identifies the snapshot that the log applies to.
+    generation: u64,
+    /// Number of records in the log.
+    records: usize,
+    compaction_threshold: usize,
+    buffer: Vec<u8>,
+}
+
+const SNAPSHOT: &str = "snapshot";
+const LOG: &str = "log";
+const SNAPSHOT_MAGIC: &[u8; 8] = b"FIFOSNAP";
+const LOG_MAGIC: &[u8; 8] = b"FIFOLOG\0";
+/// Magic and generation.
+const HEADER_LEN: usize = 16;
+/// Length of the payload, checksum of the operation and payload, and checksum of those two.
+const RECORD_HEADER_LEN: usize = 12;
+
+const PUSH: u8 = 0;
+const POP: u8 = 1;
+const REMOVE: u8 = 2;
+
+impl<T: Eq + Hash, C: Codec<T>> PersistentFIFOSet<T, C> {
+    /// Open the queue stored in `dir`, creating the directory if it doesn't exist.
+    pub fn open(dir: impl AsRef<Path>, codec: C) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let (mut set, generation) = match fs::read(dir.join(SNAPSHOT)) {
+            Ok(bytes) => read_snapshot(&bytes, &codec)?,
+            Err(error) if error.kind() == ErrorKind::NotFound => (FIFOSet::new(), 0),
+            Err(error) => return Err(error),
+        };
+
+        let mut log = OpenOptions::new().read(true).write(true).create(true).truncate(false)
+            .open(dir.join(LOG))?;
+        let mut bytes = Vec::new();
+        log.read_to_end(&mut bytes)?;
+
+        let records = match log_generation(&bytes) {
+            // A new log is put in place with its header by `compact`, so only creating the very
+            // first log can leave an incomplete header behind.
+            _ if bytes.len() < HEADER_LEN => {
+                start_log(&mut log, generation)?;
+                0
+            }
+            None => return Err(invalid("not a log")),
+            // The snapshot is replaced before the log, so a log can't be newer than the snapshot,
+            // unless the snapshot went missing.
+            Some(log_generation) if log_generation > generation => {
+                return Err(invalid("log is newer than the snapshot"));
+            }
+            // The snapshot already contains all records of a log of an older snapshot.
+            Some(log_generation) if log_generation < generation => {
+                start_log(&mut log, generation)?;
+                0
+            }
+            Some(_) => {
+                let (records, valid) = replay(&bytes[HEADER_LEN..], &mut set, &codec)?;
+                log.set_len((HEADER_LEN + valid) as u64)?;
+                log.seek(SeekFrom::End(0))?;
+                records
+            }
+        };
+
+        Ok(Self {
+            set,
+            codec,
+            dir,
+            log,
+            generation,
+            records,
+            compaction_threshold: 1024,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Add an item to the queue, like `FIFOSet::push`.
+    pub fn push(&mut self, element: T) -> io::Result<Push<T>> {
+        if self.set.contains(&element) {
+            return Ok(Push::Ignored(element));
+        }
+
+        self.buffer.clear();
+        self.codec.encode(&element, &mut self.buffer);
+        self.append(PUSH)?;
+
+        Ok(self.set.push(element))
+    }
+
+    /// Retrieve the item that has been in the queue longest.
+    pub fn pop(&mut self) -> io::Result<Option<T>> {
+        if self.set.is_empty() {
+            return Ok(None);
+        }
+
+        self.buffer.clear();
+        self.append(POP)?;
+
+        Ok(self.set.pop())
+    }
+
+    pub fn remove(&mut self, index: usize) -> io::Result<Option<T>> {
+        if index >= self.set.len() {
+            return Ok(None);
+        }
+
+        self.buffer.clear();
+        self.buffer.extend_from_slice(&(index as u64).to_le_bytes());
+        self.append(REMOVE)?;
+
+        Ok(self.set.remove(index))
+    }
+
+    /// Remove an item from the queue, wherever it is.
+    pub fn remove_value(&mut self, x: &T) -> io::Result<Option<T>> {
+        match self.set.position(x) {
+            Some(index) => self.remove(index),
+            None => Ok(None),
+        }
+    }
+
+    /// Write the queue to a new snapshot, and start a new, empty log.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let generation = self.generation + 1;
+
+        let mut snapshot = header(SNAPSHOT_MAGIC, generation).to_vec();
+        snapshot.extend_from_slice(&(self.set.len() as u64).to_le_bytes());
+        let mut value = Vec::new();
+        for element in self.set.iter() {
+            value.clear();
+            self.codec.encode(element, &mut value);
+            snapshot.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            snapshot.extend_from_slice(&value);
+        }
+        let checksum = crc32(&snapshot[HEADER_LEN..]);
+        snapshot.extend_from_slice(&checksum.to_le_bytes());
+
+        // After the snapshot is in place, the old log no longer matches its generation, so a
+        // crash before the new log is in place loses nothing.
+        replace_file(&self.dir, SNAPSHOT, &snapshot)?;
+        replace_file(&self.dir, LOG, &header(LOG_MAGIC, generation))?;
+
+        self.log = OpenOptions::new().append(true).open(self.dir.join(LOG))?;
+        self.generation = generation;
+        self.records = 0;
+
+        Ok(())
+    }
+
+    /// Compact once the log holds at least this many records, and more records than the queue
+    /// holds values. Defaults to 1024.
+    pub fn set_compaction_threshold(&mut self, records: usize) {
+        self.compaction_threshold = records;
+    }
+
+    /// Make sure all records reached the disk.
+    pub fn sync(&self) -> io::Result<()> {
+        self.log.sync_data()
+    }
+
+    /// Append a record with the given operation and the contents of `buffer` as its payload.
+    fn append(&mut self, operation: u8) -> io::Result<()> {
+        // Compact before rather than after the modification, so that a failure leaves the queue
+        // untouched.
+        if self.records >= self.compaction_threshold && self.records > self.set.len() {
+            self.compact()?;
+        }
+
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + 1 + self.buffer.len());
+        record.extend_from_slice(&(self.buffer.len() as u32).to_le_bytes());
+        record.extend_from_slice(&[0; 8]);
+        record.push(operation);
+        record.extend_from_slice(&self.buffer);
+        let checksum = crc32(&record[RECORD_HEADER_LEN..]);
+        record[4..8].copy_from_slice(&checksum.to_le_bytes());
+        let checksum = crc32(&record[..8]);
+        record[8..12].copy_from_slice(&checksum.to_le_bytes());
+
+        let end = self.log.seek(SeekFrom::End(0))?;
+        if let Err(error) = self.log.write_all(&record) {
+            // Don't leave a partial record behind for later records to follow.
+            let _ = self.log.set_len(end);
+            return Err(error);
+        }
+
+        self.records += 1;
+
+        Ok(())
+    }
+}
+
+impl<T, C> Deref for PersistentFIFOSet<T, C> {
+    type Target = FIFOSet<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.set
+    }
+}
+
+fn header(magic: &[u8; 8], generation: u64) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..8].copy_from_slice(magic);
+    header[8..].copy_from_slice(&generation.to_le_bytes());
+    header
+}
+
+/// Empty the log and write the header for the given generation.
+fn start_log(log: &mut File, generation: u64) -> io::Result<()> {
+    log.set_len(0)?;
+    log.seek(SeekFrom::Start(0))?;
+    log.write_all(&header(LOG_MAGIC, generation))
+}
+
+/// The generation in the header of a log, if it has a complete header.
+fn log_generation(bytes: &[u8]) -> Option<u64> {
+    let header = bytes.get(..HEADER_LEN)?;
+    (&header[..8] == LOG_MAGIC).then(|| u64::from_le_bytes(header[8..].try_into().unwrap()))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn read_snapshot<T: Eq + Hash, C: Codec<T>>(bytes: &[u8], codec: &C) -> io::Result<(FIFOSet<T>, u64)> {
+    if bytes.len() < HEADER_LEN + 12 || &bytes[..8] != SNAPSHOT_MAGIC {
+        return Err(invalid("not a snapshot"));
+    }
+
+    let (body, checksum) = bytes.split_at(bytes.len() - 4);
+    if crc32(&body[HEADER_LEN..]).to_le_bytes() != checksum {
+        return Err(invalid("snapshot checksum mismatch"));
+    }
+
+    let generation = u64::from_le_bytes(body[8..16].try_into().unwrap());
+    let count = u64::from_le_bytes(body[16..24].try_into().unwrap());
+
+    let mut set = FIFOSet::new();
+    let mut rest = &body[24..];
+    for _ in 0..count {
+        let (value, tail) = split_value(rest).ok_or_else(|| invalid("snapshot truncated"))?;
+        if let Push::Ignored(_) = set.push(codec.decode(value)?) {
+            return Err(invalid("duplicate value in snapshot"));
+        }
+        rest = tail;
+    }
+
+    if !rest.is_empty() {
+        return Err(invalid("trailing bytes in snapshot"));
+    }
+
+    Ok((set, generation))
+}
+
+fn split_value(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let len = u32::from_le_bytes(bytes.get(..4)?.try_into().unwrap()) as usize;
+    let value = bytes.get(4..4 + len)?;
+    Some((value, &bytes[4 + len..]))
+}
+
+/// Apply the records in `bytes` to `set`, and return the number of records and the number of
+/// bytes they take up.
+///
+/// Stops at a last record that is incomplete or fails its checksum, because it was torn by a crash
+/// while being written. Any other record that fails its checksum is an error. The length of a
+/// record has a checksum of its own, so that a corrupt length can't make a record look like the
+/// last one.
+fn replay<T: Eq + Hash, C: Codec<T>>(
+    bytes: &[u8],
+    set: &mut FIFOSet<T>,
+    codec: &C,
+) -> io::Result<(usize, usize)> {
+    let mut records = 0;
+    let mut valid = 0;
+
+    while let Some(header) = bytes.get(valid..valid + RECORD_HEADER_LEN) {
+        if crc32(&header[..8]).to_le_bytes() != header[8..] {
+            return Err(invalid("corrupt record header in log"));
+        }
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let end = valid + RECORD_HEADER_LEN + 1 + len;
+        let Some(record) = bytes.get(valid + RECORD_HEADER_LEN..end) else {
+            break;
+        };
+        if crc32(record) != checksum {
+            if end < bytes.len() {
+                return Err(invalid("corrupt record in log"));
+            }
+            break;
+        }
+
+        let payload = &record[1..];
+        match record[0] {
+            PUSH => {
+                set.push(codec.decode(payload)?);
+            }
+            POP => {
+                set.pop().ok_or_else(|| invalid("pop from an empty queue in log"))?;
+            }
+            REMOVE => {
+                let index = payload.try_into().map(u64::from_le_bytes)
+                    .map_err(|_| invalid("malformed remove in log"))?;
+                set.remove(index as usize).ok_or_else(|| invalid("remove out of bounds in log"))?;
+            }
+            _ => return Err(invalid("unknown operation in log")),
+        }
+
+        records += 1;
+        valid = end;
+    }
+
+    Ok((records, valid))
+}
+
+/// Atomically replace the file `name` in `dir` by one with the given contents.
+fn replace_file(dir: &Path, name: &str, contents: &[u8]) -> io::Result<()> {
+    let temporary = dir.join(format!("{}.tmp", name));
+    let mut file = File::create(&temporary)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temporary, dir.join(name))?;
+
+    // Persist the rename itself. Not every platform supports opening a directory.
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+/// The CRC-32 checksum used by zlib, among others.
+fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    !bytes.iter().fold(!0, |crc, &byte| TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::{self, ErrorKind, Write};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{Codec, PersistentFIFOSet, HEADER_LEN, LOG, RECORD_HEADER_LEN, SNAPSHOT};
+
+    struct U32;
+
+    impl Codec<u32> for U32 {
+        fn encode(&self, value: &u32, out: &mut Vec<u8>) {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+
+        fn decode(&self, bytes: &[u8]) -> io::Result<u32> {
+            bytes.try_into().map(u32::from_le_bytes).map_err(|_| io::Error::from(ErrorKind::InvalidData))
+        }
+    }
+
+    /// An empty directory that no other test uses.
+    fn directory() -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "fifo-set-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed),
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn contents(set: &PersistentFIFOSet<u32, U32>) -> Vec<u32> {
+        set.iter().copied().collect()
+    }
+
+    /// The length of a record that pushes a `u32`.
+    const PUSH_LEN: usize = RECORD_HEADER_LEN + 1 + 4;
+
+    #[test]
+    fn reopen() {
+        let dir = directory();
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        for value in 0..5 {
+            set.push(value).unwrap();
+        }
+        set.pop().unwrap();
+        set.remove(1).unwrap();
+        drop(set);
+
+        let set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        assert_eq!(contents(&set), [1, 3, 4]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_last_record() {
+        let dir = directory();
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        for value in 0..3 {
+            set.push(value).unwrap();
+        }
+        drop(set);
+
+        let log = OpenOptions::new().write(true).open(dir.join(LOG)).unwrap();
+        log.set_len((HEADER_LEN + 3 * PUSH_LEN - 2) as u64).unwrap();
+        drop(log);
+
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        assert_eq!(contents(&set), [0, 1]);
+
+        // The torn record is gone, so records appended after it are replayed.
+        set.push(5).unwrap();
+        drop(set);
+        let set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        assert_eq!(contents(&set), [0, 1, 5]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_last_record() {
+        let dir = directory();
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        for value in 0..3 {
+            set.push(value).unwrap();
+        }
+        drop(set);
+
+        let mut bytes = fs::read(dir.join(LOG)).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        fs::write(dir.join(LOG), &bytes).unwrap();
+
+        let set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        assert_eq!(contents(&set), [0, 1]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_middle_record() {
+        let dir = directory();
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        for value in 0..3 {
+            set.push(value).unwrap();
+        }
+        drop(set);
+
+        let mut bytes = fs::read(dir.join(LOG)).unwrap();
+        bytes[HEADER_LEN + PUSH_LEN + RECORD_HEADER_LEN + 1] ^= 1;
+        fs::write(dir.join(LOG), &bytes).unwrap();
+
+        let error = PersistentFIFOSet::open(&dir, U32).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_middle_length() {
+        let dir = directory();
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        for value in 0..5 {
+            set.push(value).unwrap();
+        }
+        drop(set);
+
+        // A length that reaches past the end of the log looks like a torn record, unless it is
+        // caught by its checksum.
+        let mut bytes = fs::read(dir.join(LOG)).unwrap();
+        bytes[HEADER_LEN + PUSH_LEN + 1] ^= 1;
+        fs::write(dir.join(LOG), &bytes).unwrap();
+
+        let error = PersistentFIFOSet::open(&dir, U32).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(fs::read(dir.join(LOG)).unwrap(), bytes);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn crash_between_snapshot_and_log() {
+        let dir = directory();
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        for value in 0..3 {
+            set.push(value).unwrap();
+        }
+        set.pop().unwrap();
+        let old_log = fs::read(dir.join(LOG)).unwrap();
+        set.compact().unwrap();
+        drop(set);
+
+        // Put back the log of the previous generation, as if the new log never replaced it.
+        fs::write(dir.join(LOG), &old_log).unwrap();
+
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        assert_eq!(contents(&set), [1, 2]);
+
+        set.push(3).unwrap();
+        drop(set);
+        let set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        assert_eq!(contents(&set), [1, 2, 3]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_snapshot() {
+        let dir = directory();
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        set.push(0).unwrap();
+        set.compact().unwrap();
+        set.push(1).unwrap();
+        drop(set);
+
+        fs::remove_file(dir.join(SNAPSHOT)).unwrap();
+
+        let error = PersistentFIFOSet::open(&dir, U32).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        // The log is left alone.
+        assert!(fs::read(dir.join(LOG)).unwrap().len() > HEADER_LEN);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn damaged_log_header() {
+        let dir = directory();
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        set.push(0).unwrap();
+        drop(set);
+
+        let mut bytes = fs::read(dir.join(LOG)).unwrap();
+        bytes[0] ^= 1;
+        fs::write(dir.join(LOG), &bytes).unwrap();
+
+        let error = PersistentFIFOSet::open(&dir, U32).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(fs::read(dir.join(LOG)).unwrap(), bytes);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn torn_log_header() {
+        let dir = directory();
+        let mut set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        set.push(0).unwrap();
+        set.compact().unwrap();
+        drop(set);
+
+        let mut log = OpenOptions::new().write(true).truncate(true).open(dir.join(LOG)).unwrap();
+        log.write_all(b"FIFO").unwrap();
+        drop(log);
+
+        let set = PersistentFIFOSet::open(&dir, U32).unwrap();
+        assert_eq!(contents(&set), [0]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
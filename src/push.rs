@@ -0,0 +1,47 @@
+/// What happened to a value passed to `FIFOSet::push`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Push<T> {
+    /// The value was added to the queue.
+    Added,
+    /// The value was already queued, so the queue was left as it was. Contains the pushed value.
+    Ignored(T),
+    /// The value was already queued and the queued value was moved to the back of the queue.
+    /// Contains the pushed value.
+    MovedToBack(T),
+    /// The value was already queued and the queued value was replaced by the pushed value, keeping
+    /// its place in the queue. Contains the value that was replaced.
+    Replaced(T),
+    /// The value was already queued, which the duplicate policy treats as an error. Contains the
+    /// pushed value.
+    Duplicate(T),
+    /// The value was added to a full queue, after evicting the front value. Contains the evicted
+    /// value.
+    Evicted(T),
+    /// The queue was full, so the value was not added. Contains the pushed value.
+    Rejected(T),
+}
+
+/// What to do when pushing a new value onto a queue that has reached its limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Evict the value at the front of the queue, the one `pop` would return, to make room.
+    DropOldest,
+    /// Don't add the new value.
+    Reject,
+    /// Panic.
+    Panic,
+}
+
+/// What to do when pushing a value that is already in the queue.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Leave the queue as it is.
+    #[default]
+    Ignore,
+    /// Move the queued value to the back of the queue.
+    MoveToBack,
+    /// Replace the queued value by the pushed one, keeping its place in the queue.
+    Replace,
+    /// Leave the queue as it is and report the push as a `Push::Duplicate`.
+    Error,
+}
@@ -0,0 +1,156 @@
+//! Serialization of a `FIFOSet` as a sequence of its values, front to back.
+//!
+//! Deserializing a sequence that contains a value more than once fails by default. Use
+//! `FIFOSetSeed` or `deserialize_ignoring_duplicates` to handle duplicates with another
+//! `DuplicatePolicy` instead.
+
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+
+use ::serde::de::{DeserializeSeed, Error, SeqAccess, Visitor};
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{DuplicatePolicy, FIFOSet, Push};
+
+impl<T: Serialize, S> Serialize for FIFOSet<T, S> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de, T, S> Deserialize<'de> for FIFOSet<T, S>
+where
+    T: Deserialize<'de> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        FIFOSetSeed::new(DuplicatePolicy::Error).deserialize(deserializer)
+    }
+}
+
+/// Deserialize a `FIFOSet`, keeping only the first occurrence of each value.
+///
+/// Meant for `#[serde(deserialize_with = "fifo_set::serde::deserialize_ignoring_duplicates")]`.
+pub fn deserialize_ignoring_duplicates<'de, D, T, S>(deserializer: D) -> Result<FIFOSet<T, S>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    FIFOSetSeed::new(DuplicatePolicy::Ignore).deserialize(deserializer)
+}
+
+/// Deserializes a `FIFOSet`, pushing the values in the sequence with the given duplicate policy.
+///
+/// With `DuplicatePolicy::Error`, a duplicate fails deserialization.
+pub struct FIFOSetSeed<T, S> {
+    duplicate: DuplicatePolicy,
+    marker: PhantomData<fn() -> FIFOSet<T, S>>,
+}
+
+impl<T, S> FIFOSetSeed<T, S> {
+    pub fn new(duplicate: DuplicatePolicy) -> Self {
+        Self { duplicate, marker: PhantomData }
+    }
+}
+
+impl<'de, T, S> DeserializeSeed<'de> for FIFOSetSeed<T, S>
+where
+    T: Deserialize<'de> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    type Value = FIFOSet<T, S>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T, S> Visitor<'de> for FIFOSetSeed<T, S>
+where
+    T: Deserialize<'de> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    type Value = FIFOSet<T, S>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.duplicate {
+            DuplicatePolicy::Error => formatter.write_str("a sequence of unique values"),
+            _ => formatter.write_str("a sequence"),
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        // Don't trust the size hint with a large allocation.
+        let capacity = seq.size_hint().unwrap_or(0).min(4096);
+        let mut set = FIFOSet::with_capacity_and_hasher(capacity, S::default());
+
+        let mut index = 0;
+        while let Some(value) = seq.next_element()? {
+            if let Push::Duplicate(value) = set.push_with(value, self.duplicate) {
+                // Nothing was dropped so far, so indices in the set and the sequence agree.
+                let earlier = set.position(&value).unwrap_or_default();
+                return Err(A::Error::custom(format_args!(
+                    "duplicate value at index {}, equal to the value at index {}",
+                    index,
+                    earlier,
+                )));
+            }
+            index += 1;
+        }
+
+        Ok(set)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use ::serde::de::DeserializeSeed;
+
+    use crate::{DuplicatePolicy, FIFOSet, Push};
+
+    use super::{deserialize_ignoring_duplicates, FIFOSetSeed};
+
+    #[test]
+    fn round_trip() {
+        let mut set = FIFOSet::from([1, 2, 3]);
+        set.push_front(4);
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, "[4,1,2,3]");
+
+        let mut loaded = serde_json::from_str::<FIFOSet<u32>>(&json).unwrap();
+        assert_eq!(loaded, set);
+        assert!(loaded.contains(&2));
+        assert_eq!(loaded.position(&3), Some(3));
+        assert_eq!(loaded.push(1), Push::Ignored(1));
+        assert_eq!(loaded.pop(), Some(4));
+    }
+
+    #[test]
+    fn duplicate() {
+        let error = serde_json::from_str::<FIFOSet<u32>>("[1,2,3,2]").unwrap_err();
+        // `serde_json` appends the location.
+        let message = error.to_string();
+        assert!(message.starts_with("duplicate value at index 3, equal to the value at index 1 "));
+
+        let error = serde_json::from_str::<FIFOSet<u32>>("{}").unwrap_err();
+        assert!(error.to_string().contains("a sequence of unique values"));
+    }
+
+    #[test]
+    fn ignoring_duplicates() {
+        let mut deserializer = serde_json::Deserializer::from_str("[1,2,1,3,2]");
+        let set: FIFOSet<u32> = deserialize_ignoring_duplicates(&mut deserializer).unwrap();
+        assert_eq!(Vec::from(set), [1, 2, 3]);
+    }
+
+    #[test]
+    fn seed() {
+        let mut deserializer = serde_json::Deserializer::from_str("[1,2,1,3,2]");
+        let set: FIFOSet<u32> = FIFOSetSeed::new(DuplicatePolicy::MoveToBack)
+            .deserialize(&mut deserializer)
+            .unwrap();
+        assert_eq!(Vec::from(set), [1, 3, 2]);
+    }
+}
@@ -0,0 +1,108 @@
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+
+/// Storage for values that hands out a stable key for each value.
+///
+/// Keys of removed values are reused by later insertions.
+#[derive(Clone)]
+pub(crate) struct Slab<T> {
+    entries: Vec<Entry<T>>,
+    next_free: usize,
+    len: usize,
+}
+
+#[derive(Clone)]
+enum Entry<T> {
+    Occupied(T),
+    Vacant(usize),
+}
+
+impl<T> Slab<T> {
+    pub(crate) fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+            next_free: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        let free = self.entries.len() - self.len;
+        self.entries.reserve(additional.saturating_sub(free));
+    }
+
+    pub(crate) fn get(&self, key: usize) -> Option<&T> {
+        match self.entries.get(key) {
+            Some(Entry::Occupied(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        match self.entries.get_mut(key) {
+            Some(Entry::Occupied(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, value: T) -> usize {
+        let key = self.next_free;
+
+        if key == self.entries.len() {
+            self.entries.push(Entry::Occupied(value));
+            self.next_free = key + 1;
+        } else {
+            match core::mem::replace(&mut self.entries[key], Entry::Occupied(value)) {
+                Entry::Vacant(next) => self.next_free = next,
+                Entry::Occupied(_) => unreachable!("free list points at an occupied entry"),
+            }
+        }
+
+        self.len += 1;
+        key
+    }
+
+    pub(crate) fn remove(&mut self, key: usize) -> T {
+        match core::mem::replace(&mut self.entries[key], Entry::Vacant(self.next_free)) {
+            Entry::Occupied(value) => {
+                self.next_free = key;
+                self.len -= 1;
+                value
+            }
+            vacant => {
+                self.entries[key] = vacant;
+                panic!("no value stored under key {}", key)
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.next_free = 0;
+        self.len = 0;
+    }
+}
+
+impl<T> Default for Slab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Index<usize> for Slab<T> {
+    type Output = T;
+
+    fn index(&self, key: usize) -> &Self::Output {
+        self.get(key).expect("no value stored under key")
+    }
+}
+
+impl<T> IndexMut<usize> for Slab<T> {
+    fn index_mut(&mut self, key: usize) -> &mut Self::Output {
+        self.get_mut(key).expect("no value stored under key")
+    }
+}
@@ -0,0 +1,180 @@
+use core::hash::{BuildHasher, Hash};
+
+use hashbrown::HashSet;
+
+use crate::{DefaultHashBuilder, FIFOSet, Push};
+
+/// A queue of work items that keeps track of the items being processed.
+///
+/// An item taken from the queue with `get` is processing until `done` is called for it. Pushing
+/// an item while it is processing doesn't queue it right away, so that no two workers process
+/// the same item at the same time. Instead, the item is marked dirty and queued once it is done.
+///
+/// Every operation takes `&mut self`, and `get` doesn't wait for an item, so this queue is meant
+/// for a single thread. Workers on different threads share a `ConcurrentWorkQueue` instead, which
+/// needs the `std` feature.
+#[derive(Clone)]
+pub struct WorkQueue<T, S = DefaultHashBuilder> {
+    queue: FIFOSet<T, S>,
+    /// Items pushed while they were processing.
+    dirty: HashSet<T, S>,
+    processing: HashSet<T, S>,
+}
+
+#[cfg(feature = "std")]
+impl<T: Clone + Eq + Hash> WorkQueue<T> {
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::new())
+    }
+}
+
+impl<T: Clone + Eq + Hash, S: BuildHasher + Clone> WorkQueue<T, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            queue: FIFOSet::with_hasher(hasher.clone()),
+            dirty: HashSet::with_hasher(hasher.clone()),
+            processing: HashSet::with_hasher(hasher),
+        }
+    }
+
+    /// Add an item to the queue, or mark it dirty if it is processing.
+    ///
+    /// Returns whether the item wasn't queued or dirty already.
+    pub fn push(&mut self, item: T) -> bool {
+        if self.processing.contains(&item) {
+            self.dirty.insert(item)
+        } else {
+            matches!(self.queue.push(item), Push::Added)
+        }
+    }
+
+    /// Take the item that has been in the queue longest, and mark it processing.
+    pub fn get(&mut self) -> Option<T> {
+        let item = self.queue.pop()?;
+        self.processing.insert(item.clone());
+        Some(item)
+    }
+
+    /// Mark an item as no longer processing. If it was pushed while processing, queue it.
+    ///
+    /// Returns whether the item was processing.
+    pub fn done(&mut self, item: &T) -> bool {
+        if !self.processing.remove(item) {
+            return false;
+        }
+
+        if let Some(item) = self.dirty.take(item) {
+            self.queue.push(item);
+        }
+
+        true
+    }
+
+    /// Mark an item as no longer processing and queue it again right away, for example because
+    /// processing failed.
+    ///
+    /// Returns whether the item was processing.
+    pub fn requeue(&mut self, item: &T) -> bool {
+        let Some(item) = self.processing.take(item) else {
+            return false;
+        };
+
+        self.dirty.remove(&item);
+        self.queue.push(item);
+
+        true
+    }
+
+    pub fn is_processing(&self, item: &T) -> bool {
+        self.processing.contains(item)
+    }
+
+    /// Whether the item is queued or dirty, that is, whether it still needs to be processed.
+    pub fn is_pending(&self, item: &T) -> bool {
+        self.queue.contains(item) || self.dirty.contains(item)
+    }
+
+    /// The number of items in the queue, not counting dirty items.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// The number of items that are processing.
+    pub fn processing_len(&self) -> usize {
+        self.processing.len()
+    }
+}
+
+impl<T, S: Default> Default for WorkQueue<T, S> {
+    fn default() -> Self {
+        Self {
+            queue: FIFOSet::default(),
+            dirty: HashSet::default(),
+            processing: HashSet::default(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::WorkQueue;
+
+    #[test]
+    fn push_while_processing() {
+        let mut queue = WorkQueue::new();
+        assert!(queue.push(1));
+        assert_eq!(queue.get(), Some(1));
+        assert!(queue.is_processing(&1));
+
+        // The item isn't handed out again while it is processing.
+        assert!(queue.push(1));
+        assert!(!queue.push(1));
+        assert_eq!(queue.len(), 0);
+        assert!(queue.is_pending(&1));
+        assert_eq!(queue.get(), None);
+
+        assert!(queue.done(&1));
+        assert!(!queue.is_processing(&1));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.get(), Some(1));
+        assert!(queue.done(&1));
+        assert_eq!(queue.get(), None);
+        assert!(!queue.is_pending(&1));
+    }
+
+    #[test]
+    fn done_without_push() {
+        let mut queue = WorkQueue::new();
+        queue.push(1);
+        queue.push(2);
+        assert_eq!(queue.get(), Some(1));
+
+        assert!(queue.done(&1));
+        assert!(!queue.done(&1));
+        assert!(!queue.done(&2));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.processing_len(), 0);
+    }
+
+    #[test]
+    fn requeue() {
+        let mut queue = WorkQueue::new();
+        queue.push(1);
+        queue.push(2);
+        assert_eq!(queue.get(), Some(1));
+        queue.push(1);
+
+        assert!(queue.requeue(&1));
+        assert!(!queue.requeue(&1));
+        // Queued once, behind the items that were already queued.
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.get(), Some(2));
+        assert_eq!(queue.get(), Some(1));
+        assert!(queue.done(&1));
+        assert_eq!(queue.get(), None);
+    }
+}